@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::PrimaryWindow;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use clicky_rs::binding::Binding;
+use clicky_rs::config::Config;
+use clicky_rs::input::InputEvent;
+use clicky_rs::keys::{spawn_trail, update_trails, Trail, TrailMaterial, TrailMesh, TrailStart, KEY_SIZE};
+
+// Numbers of trails spawned per iteration, roughly a long stream session's worth of presses.
+const TRAIL_COUNTS: [usize; 2] = [10_000, 50_000];
+
+const KEY: Binding = Binding::Key(KeyCode::KeyQ);
+const START: TrailStart = TrailStart {
+    origin: Vec2::ZERO,
+    width: KEY_SIZE,
+};
+
+// A headless app with just what the trail systems need.
+fn trail_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_resource::<Config>()
+        .init_resource::<TrailMesh>()
+        .add_event::<InputEvent>()
+        .add_systems(Update, update_trails);
+    app.world_mut().spawn((Window::default(), PrimaryWindow));
+    app
+}
+
+// Spawn trails the way the overlay does, sharing the trail mesh and the key's material.
+fn spawn_shared(app: &mut App, count: usize) {
+    let material = TrailMaterial(app.world_mut().resource_mut::<Assets<ColorMaterial>>().add(Color::WHITE));
+    app.world_mut().run_system_once(move |mut commands: Commands, mesh: Res<TrailMesh>, config: Res<Config>| {
+        for _ in 0..count {
+            let trail = Trail::new(KEY, &START, Duration::ZERO);
+            spawn_trail(&mut commands, &mesh, &material, trail, START.width, &config.layout, Duration::ZERO);
+        }
+    });
+}
+
+// Spawn trails with a mesh and a material of their own, the way the overlay used to.
+fn spawn_per_press(app: &mut App, count: usize) {
+    app.world_mut().run_system_once(
+        move |mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<ColorMaterial>>| {
+            for _ in 0..count {
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(Rectangle::new(KEY_SIZE, 1.0))),
+                        material: materials.add(Color::WHITE),
+                        ..Default::default()
+                    },
+                    Trail::new(KEY, &START, Duration::ZERO),
+                ));
+            }
+        },
+    );
+}
+
+fn asset_counts(app: &App) -> (usize, usize) {
+    let world = app.world();
+    (world.resource::<Assets<Mesh>>().len(), world.resource::<Assets<ColorMaterial>>().len())
+}
+
+fn spawn_trails(c: &mut Criterion) {
+    // Report how many assets each approach leaves behind, which is what grew without bound.
+    for (name, spawn) in [("shared", spawn_shared as fn(&mut App, usize)), ("per press", spawn_per_press)] {
+        let mut app = trail_app();
+        spawn(&mut app, TRAIL_COUNTS[0]);
+        let (meshes, materials) = asset_counts(&app);
+        println!("{name}: {meshes} meshes and {materials} materials after {} trails", TRAIL_COUNTS[0]);
+    }
+
+    let mut group = c.benchmark_group("spawn_trails");
+    group.sample_size(10);
+    for count in TRAIL_COUNTS {
+        group.bench_with_input(BenchmarkId::new("shared", count), &count, |b, &count| {
+            b.iter_batched(
+                trail_app,
+                |mut app| {
+                    spawn_shared(&mut app, count);
+                    app.update();
+                    app
+                },
+                BatchSize::LargeInput,
+            );
+        });
+        group.bench_with_input(BenchmarkId::new("per_press", count), &count, |b, &count| {
+            b.iter_batched(
+                trail_app,
+                |mut app| {
+                    spawn_per_press(&mut app, count);
+                    app.update();
+                    app
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn move_trails(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_trails");
+    for count in TRAIL_COUNTS {
+        let mut app = trail_app();
+        spawn_shared(&mut app, count);
+        // Trails are only despawned once their key is released, and these are never released.
+        app.update();
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| b.iter(|| app.update()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, spawn_trails, move_trails);
+criterion_main!(benches);
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use bevy::reflect::Enum;
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// An input that can be shown as a key on the overlay.
+// Bindings are written as strings: a `KeyCode` name (e.g. "KeyQ"), a mouse button
+// ("MouseLeft", "MouseRight", "MouseMiddle", "MouseBack", "MouseForward"),
+// a scroll direction ("ScrollUp", "ScrollDown"), or a gamepad button prefixed by
+// "Gamepad" and an optional gamepad id (e.g. "GamepadSouth" for any gamepad,
+// "Gamepad1RightTrigger2" for the right trigger of gamepad 1).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // Scrolling has no held state, so each wheel tick is a press released on the next frame.
+    ScrollUp,
+    ScrollDown,
+    // A gamepad button on a specific gamepad, or on any gamepad when `gamepad` is `None`.
+    GamepadButton {
+        gamepad: Option<usize>,
+        button: GamepadButtonType,
+    },
+}
+
+// Prefix of gamepad button bindings in the configuration.
+const GAMEPAD_PREFIX: &str = "Gamepad";
+
+// Analog triggers, which count as pressed once they pass the configured threshold.
+pub const TRIGGERS: [GamepadButtonType; 2] = [GamepadButtonType::LeftTrigger2, GamepadButtonType::RightTrigger2];
+
+// Names of the supported mouse buttons in the configuration.
+const MOUSE_BUTTONS: [(&str, MouseButton); 5] = [
+    ("MouseLeft", MouseButton::Left),
+    ("MouseRight", MouseButton::Right),
+    ("MouseMiddle", MouseButton::Middle),
+    ("MouseBack", MouseButton::Back),
+    ("MouseForward", MouseButton::Forward),
+];
+
+impl Binding {
+    // Short text drawn on the key when no label is configured.
+    pub fn default_label(&self) -> String {
+        match self {
+            Binding::Key(key_code) => key_code.variant_name().replace("Key", ""), // Remove "Key" prefix for display
+            Binding::Mouse(MouseButton::Left) => "LMB".to_string(),
+            Binding::Mouse(MouseButton::Right) => "RMB".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "MMB".to_string(),
+            Binding::Mouse(MouseButton::Back) => "M4".to_string(),
+            Binding::Mouse(MouseButton::Forward) => "M5".to_string(),
+            Binding::Mouse(MouseButton::Other(index)) => format!("M{index}"),
+            Binding::ScrollUp => "WU".to_string(),
+            Binding::ScrollDown => "WD".to_string(),
+            Binding::GamepadButton { button, .. } => gamepad_button_label(*button),
+        }
+    }
+}
+
+// Short names of gamepad buttons, using the common Xbox layout.
+fn gamepad_button_label(button: GamepadButtonType) -> String {
+    let label = match button {
+        GamepadButtonType::South => "A",
+        GamepadButtonType::East => "B",
+        GamepadButtonType::North => "Y",
+        GamepadButtonType::West => "X",
+        GamepadButtonType::C => "C",
+        GamepadButtonType::Z => "Z",
+        GamepadButtonType::LeftTrigger => "LB",
+        GamepadButtonType::LeftTrigger2 => "LT",
+        GamepadButtonType::RightTrigger => "RB",
+        GamepadButtonType::RightTrigger2 => "RT",
+        GamepadButtonType::Select => "Sel",
+        GamepadButtonType::Start => "St",
+        GamepadButtonType::Mode => "Mode",
+        GamepadButtonType::LeftThumb => "LS",
+        GamepadButtonType::RightThumb => "RS",
+        GamepadButtonType::DPadUp => "Up",
+        GamepadButtonType::DPadDown => "Dn",
+        GamepadButtonType::DPadLeft => "Lt",
+        GamepadButtonType::DPadRight => "Rt",
+        GamepadButtonType::Other(index) => return format!("B{index}"),
+    };
+    label.to_string()
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key_code) => f.write_str(key_code.variant_name()),
+            Binding::Mouse(button) => match MOUSE_BUTTONS.iter().find(|(_, other)| other == button) {
+                Some((name, _)) => f.write_str(name),
+                None => write!(f, "{button:?}"),
+            },
+            Binding::ScrollUp => f.write_str("ScrollUp"),
+            Binding::ScrollDown => f.write_str("ScrollDown"),
+            Binding::GamepadButton { gamepad, button } => {
+                f.write_str(GAMEPAD_PREFIX)?;
+                if let Some(id) = gamepad {
+                    write!(f, "{id}")?;
+                }
+                f.write_str(button.variant_name())
+            }
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some((_, button)) = MOUSE_BUTTONS.iter().find(|(other, _)| *other == name) {
+            return Ok(Binding::Mouse(*button));
+        }
+
+        if let Some(rest) = name.strip_prefix(GAMEPAD_PREFIX) {
+            // An optional gamepad id sits between the prefix and the button name.
+            let button_start = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let (id, button) = rest.split_at(button_start);
+            let gamepad = match id {
+                "" => None,
+                id => Some(id.parse().map_err(|_| format!("invalid gamepad id in {name:?}"))?),
+            };
+            let button = GamepadButtonType::deserialize(StrDeserializer::<ValueError>::new(button))
+                .map_err(|_| format!("unknown gamepad button in {name:?}"))?;
+            return Ok(Binding::GamepadButton { gamepad, button });
+        }
+
+        match name {
+            "ScrollUp" => Ok(Binding::ScrollUp),
+            "ScrollDown" => Ok(Binding::ScrollDown),
+            _ => KeyCode::deserialize(StrDeserializer::<ValueError>::new(name))
+                .map(Binding::Key)
+                .map_err(|_| format!("unknown key, mouse button, scroll direction or gamepad button {name:?}")),
+        }
+    }
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
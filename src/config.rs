@@ -0,0 +1,465 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer};
+
+use crate::binding::Binding;
+
+// Default location of the configuration file, relative to the working directory.
+pub const DEFAULT_CONFIG_PATH: &str = "clicky.toml";
+
+// How often the configuration file is checked for changes, in seconds.
+const WATCH_INTERVAL: f32 = 0.5;
+
+// The overlay configuration as read from the TOML file.
+#[derive(Resource, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub keys: Vec<KeyConfig>,
+    #[serde(default)]
+    pub gamepad: GamepadConfig,
+    #[serde(default)]
+    pub hotkeys: HotkeysConfig,
+    #[serde(default)]
+    pub input: InputConfig,
+    #[serde(default)]
+    pub window: WindowConfig,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    #[serde(default)]
+    pub sound: SoundConfig,
+}
+
+// A single key shown on the overlay.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct KeyConfig {
+    pub code: Binding,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+    pub label: Option<String>,
+    // Sounds played when the key is pressed and released, as paths inside the assets directory.
+    // The press sound falls back to the sound pack's, and the release sound is optional.
+    pub sound: Option<String>,
+    pub release_sound: Option<String>,
+    // Volume of the key's sounds, overriding the sound pack's and the global one.
+    pub volume: Option<f32>,
+    // Placement in key units, a unit being a key and the gap after it. Keys fill their row from the
+    // left, `x` and `y` shift a key (leaving a gap after the previous one), and `width` and `height`
+    // stretch it, e.g. 2.0 for a double-width key. Row 0 is the one nearest to the trails.
+    #[serde(default)]
+    pub row: usize,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+// Settings shared by all gamepad bindings.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadConfig {
+    // How far an analog trigger must be pulled, from 0.0 to 1.0, to count as pressed.
+    pub trigger_threshold: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self { trigger_threshold: 0.5 }
+    }
+}
+
+// Where presses and releases are read from.
+// Changes to this section only take effect after a restart.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    pub backend: InputBackend,
+    // Devices read by the evdev backend, by name (e.g. "AT Translated Set 2 keyboard") or path
+    // (e.g. "/dev/input/event3"). Every keyboard and mouse is read when empty.
+    pub devices: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InputBackend {
+    // The overlay's own window, which only sees keys while it has focus.
+    #[default]
+    Window,
+    // Linux input devices, read regardless of which window has focus.
+    Evdev,
+    // Raw XInput 2 events from the X server, seen regardless of focus without extra permissions.
+    X11,
+}
+
+impl InputBackend {
+    // Name of the backend in the configuration, which is also the cargo feature providing it.
+    pub fn name(self) -> &'static str {
+        match self {
+            InputBackend::Window => "window",
+            InputBackend::Evdev => "evdev",
+            InputBackend::X11 => "x11",
+        }
+    }
+}
+
+// How the window is shown. Changes to this section only take effect after a restart.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    // Transparent, borderless and always on top, for streaming or playing next to the overlay.
+    pub overlay: bool,
+    // Let clicks pass through the overlay to the windows below it.
+    pub click_through: bool,
+}
+
+// How the keys and their trails are arranged.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    // Where the trails go; the keys sit on the opposite edge of the window.
+    pub direction: TrailDirection,
+    // Size of the keys and the gap between them in pixels, in a window of the initial size.
+    pub key_size: f32,
+    pub key_spacing: f32,
+    // How fast trails move away from their keys, in pixels per second.
+    pub trail_speed: f32,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            direction: TrailDirection::default(),
+            key_size: 80.0,
+            key_spacing: 10.0,
+            trail_speed: 250.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailDirection {
+    #[default]
+    Up,
+    // Keys at the top with trails falling down, like a mania receptor view.
+    Down,
+    // Keys in a column with trails moving sideways, like Taiko.
+    Left,
+    Right,
+}
+
+impl TrailDirection {
+    // Unit vector the trails move along.
+    pub fn vector(self) -> Vec2 {
+        match self {
+            TrailDirection::Up => Vec2::Y,
+            TrailDirection::Down => Vec2::NEG_Y,
+            TrailDirection::Left => Vec2::NEG_X,
+            TrailDirection::Right => Vec2::X,
+        }
+    }
+
+    // Unit vector the keys are lined up along, in configuration order: left to right, or top to bottom.
+    pub fn across(self) -> Vec2 {
+        if self.is_vertical() {
+            Vec2::X
+        } else {
+            Vec2::NEG_Y
+        }
+    }
+
+    pub fn is_vertical(self) -> bool {
+        matches!(self, TrailDirection::Up | TrailDirection::Down)
+    }
+}
+
+// Hitsound settings shared by all keys.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundConfig {
+    // Volume every sound is multiplied by, changed at runtime with the volume hotkeys.
+    pub master_volume: f32,
+    // Volume of the sounds of keys that don't set their own, from 0.0 upwards.
+    pub volume: f32,
+    // How much each sound's volume and pitch randomly vary, as a fraction (e.g. 0.1 for up to 10% either way).
+    pub volume_variation: f32,
+    pub pitch_variation: f32,
+    // Sound pack used at startup, by the name of its directory in `assets/sounds`.
+    pub pack: Option<String>,
+    // Most sounds playing at once; presses beyond it are silent until a sound finishes.
+    pub max_voices: usize,
+    // Shortest time between two sounds in milliseconds, so fast bursts don't pile up.
+    pub min_interval_ms: u64,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            volume: 0.2,
+            volume_variation: 0.0,
+            pitch_variation: 0.0,
+            pack: None,
+            max_voices: 8,
+            min_interval_ms: 0,
+        }
+    }
+}
+
+// Bindings that control the overlay itself.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeysConfig {
+    // Stops an ongoing recording and writes the replay file.
+    pub stop_recording: Binding,
+    // Brings back the window decorations so the overlay can be dragged, and locks it again.
+    pub move_overlay: Binding,
+    // Switches to the next sound pack, going back to the default sounds after the last one.
+    pub next_sound_pack: Binding,
+    // Mute and unmute every sound, and change the master volume in steps.
+    pub mute: Binding,
+    pub volume_up: Binding,
+    pub volume_down: Binding,
+    // Replay controls, read from the keyboard since the replay drives the bindings.
+    pub replay_pause: KeyCode,
+    pub replay_faster: KeyCode,
+    pub replay_slower: KeyCode,
+    pub replay_forward: KeyCode,
+    pub replay_backward: KeyCode,
+}
+
+impl Default for HotkeysConfig {
+    fn default() -> Self {
+        Self {
+            stop_recording: Binding::Key(KeyCode::F10),
+            move_overlay: Binding::Key(KeyCode::F9),
+            next_sound_pack: Binding::Key(KeyCode::F8),
+            mute: Binding::Key(KeyCode::F7),
+            volume_up: Binding::Key(KeyCode::F6),
+            volume_down: Binding::Key(KeyCode::F5),
+            replay_pause: KeyCode::Space,
+            replay_faster: KeyCode::ArrowUp,
+            replay_slower: KeyCode::ArrowDown,
+            replay_forward: KeyCode::ArrowRight,
+            replay_backward: KeyCode::ArrowLeft,
+        }
+    }
+}
+
+impl KeyConfig {
+    // A key with its default label and sounds.
+    pub fn new(code: Binding, color: Color) -> Self {
+        Self {
+            code,
+            color,
+            label: None,
+            sound: None,
+            release_sound: None,
+            volume: None,
+            row: 0,
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    // Text drawn on the key, falling back to a short name of the binding.
+    pub fn label(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => self.code.default_label(),
+        }
+    }
+}
+
+impl Default for Config {
+    // The layout used when no configuration file exists: Q, W and C in red, green and blue.
+    fn default() -> Self {
+        let key = KeyConfig::new;
+
+        Self {
+            keys: vec![
+                key(Binding::Key(KeyCode::KeyQ), Color::srgb(1.0, 0.0, 0.0)),
+                key(Binding::Key(KeyCode::KeyW), Color::srgb(0.0, 1.0, 0.0)),
+                key(Binding::Key(KeyCode::KeyC), Color::srgb(0.0, 0.0, 1.0)),
+            ],
+            gamepad: GamepadConfig::default(),
+            hotkeys: HotkeysConfig::default(),
+            input: InputConfig::default(),
+            window: WindowConfig::default(),
+            layout: LayoutConfig::default(),
+            sound: SoundConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // Read and validate the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let source = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        let config: Config = toml::from_str(&source).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+        config.validate().map_err(|message| ConfigError::Invalid(path.to_path_buf(), message))?;
+        Ok(config)
+    }
+
+    // Read the configuration file at `path`, using the default layout if it does not exist.
+    pub fn load_or_default(path: &Path) -> Result<Self, ConfigError> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            info!("No configuration found at {}, using the default layout", path.display());
+            Ok(Self::default())
+        }
+    }
+
+    // Check what the file format alone cannot, like duplicate keys and out of range values.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.is_empty() {
+            return Err("at least one key must be configured".to_string());
+        }
+
+        let threshold = self.gamepad.trigger_threshold;
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            return Err(format!("gamepad trigger threshold must be between 0 and 1, got {threshold}"));
+        }
+
+        for (i, key) in self.keys.iter().enumerate() {
+            if self.keys[..i].iter().any(|other| other.code == key.code) {
+                return Err(format!("key {} is configured more than once", key.code));
+            }
+            if !key.volume.is_none_or(|volume| volume.is_finite() && volume >= 0.0) {
+                return Err(format!("volume of key {} must be finite and not negative", key.code));
+            }
+            // Infinite and NaN sizes and offsets would break the layout, so they are rejected too
+            let positive = |size: Option<f32>| size.is_none_or(|size| size.is_finite() && size > 0.0);
+            if !positive(key.width) || !positive(key.height) {
+                return Err(format!("width and height of key {} must be positive", key.code));
+            }
+            if !key.x.is_none_or(f32::is_finite) || !key.y.is_none_or(f32::is_finite) {
+                return Err(format!("x and y of key {} must be finite", key.code));
+            }
+        }
+
+        // The window backend only sees keys while the overlay has focus, which it can't get once clicks pass
+        // through it, so the `move_overlay` hotkey could never bring it back. A replay keeps the backend running
+        // for the hotkeys, so the same holds in replay mode.
+        if self.window.overlay && self.window.click_through && matches!(self.input.backend, InputBackend::Window) {
+            return Err("window click_through needs the evdev or x11 input backend".to_string());
+        }
+
+        let layout = &self.layout;
+        // Infinite and NaN sizes would make every position NaN, so they are rejected too
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        if !positive(layout.key_size) || !positive(layout.trail_speed) {
+            return Err("key size and trail speed must be positive".to_string());
+        }
+        if !(layout.key_spacing.is_finite() && layout.key_spacing >= 0.0) {
+            return Err(format!("key spacing must not be negative, got {}", layout.key_spacing));
+        }
+
+        let sound = &self.sound;
+        let volume = |value: f32| value.is_finite() && value >= 0.0;
+        if !volume(sound.volume) || !volume(sound.master_volume) {
+            return Err("sound volumes must not be negative".to_string());
+        }
+        if sound.max_voices == 0 {
+            return Err("sound max_voices must be at least 1".to_string());
+        }
+        for (name, variation) in [("volume", sound.volume_variation), ("pitch", sound.pitch_variation)] {
+            if !(0.0..1.0).contains(&variation) {
+                return Err(format!("sound {name} variation must be at least 0 and below 1, got {variation}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Resource that watches the configuration file by polling its modification time.
+#[derive(Resource)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified_time(&path),
+            path,
+            timer: Timer::from_seconds(WATCH_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+// Event sent after the configuration resource was replaced by a freshly loaded file.
+#[derive(Event)]
+pub struct ConfigReloaded;
+
+// System to reload the configuration when the file changes on disk.
+// A file that fails to load is reported and the current layout is kept.
+pub fn watch_config(
+    time: Res<Time>,
+    mut watcher: ResMut<ConfigWatcher>,
+    mut config: ResMut<Config>,
+    mut reloaded: EventWriter<ConfigReloaded>,
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // A missing file (e.g. while an editor replaces it) keeps the current layout.
+    let modified = modified_time(&watcher.path);
+    if modified.is_none() || modified == watcher.modified {
+        return;
+    }
+    watcher.modified = modified;
+
+    match Config::load(&watcher.path) {
+        Ok(new_config) => {
+            info!("Reloaded configuration from {}", watcher.path.display());
+            *config = new_config;
+            reloaded.send(ConfigReloaded);
+        }
+        Err(err) => error!("{err}; keeping the current layout"),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Errors that can occur while loading the configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "could not read {}: {err}", path.display()),
+            ConfigError::Parse(path, err) => write!(f, "could not parse {}: {err}", path.display()),
+            ConfigError::Invalid(path, message) => write!(f, "invalid configuration in {}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Colors are written as hex strings, e.g. "#ff0000" or "ff0000".
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Srgba::hex(&hex)
+        .map(Color::from)
+        .map_err(|err| serde::de::Error::custom(format!("invalid color {hex:?}: {err}")))
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use crossbeam_channel::Sender;
+use evdev::{Device, EventType, InputEvent as DeviceEvent, Key, RelativeAxisType};
+
+use crate::binding::Binding;
+use crate::input::{ChannelSource, ExternalInput};
+use crate::scancode::binding_from_linux_code;
+
+// Start reading the configured `/dev/input/event*` devices on background threads and return the source they feed.
+// Devices are picked by name or path; an empty list picks every keyboard and mouse.
+pub fn spawn_evdev_source(wanted: &[String]) -> Result<ChannelSource, String> {
+    // Devices we are not allowed to open are skipped by the enumeration.
+    let devices: Vec<(PathBuf, Device)> = evdev::enumerate()
+        .filter(|(path, device)| is_selected(path, device, wanted))
+        .collect();
+    if devices.is_empty() {
+        return Err(
+            "no readable input device found under /dev/input (reading it usually requires membership of the `input` group)"
+                .to_string(),
+        );
+    }
+
+    for name in wanted {
+        if !devices.iter().any(|(path, device)| is_named(name, path, device)) {
+            warn!("No readable input device matches {name:?}");
+        }
+    }
+
+    let (source, sender) = ChannelSource::new();
+    for (path, device) in devices {
+        info!("Capturing input from {} ({})", device.name().unwrap_or("unnamed device"), path.display());
+        let sender = sender.clone();
+        thread::Builder::new()
+            .name(format!("evdev {}", path.display()))
+            .spawn(move || {
+                if let Err(err) = read_device(device, &sender) {
+                    error!("Stopped reading {}: {err}", path.display());
+                }
+            })
+            .map_err(|err| format!("could not start the evdev reader thread: {err}"))?;
+    }
+    Ok(source)
+}
+
+fn is_selected(path: &Path, device: &Device, wanted: &[String]) -> bool {
+    if wanted.is_empty() {
+        return device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(Key::KEY_A) || keys.contains(Key::BTN_LEFT));
+    }
+    wanted.iter().any(|name| is_named(name, path, device))
+}
+
+fn is_named(name: &str, path: &Path, device: &Device) -> bool {
+    path == Path::new(name) || device.name() == Some(name)
+}
+
+// Block on the device and send its events, stamped with the kernel's time, until it is unplugged or nobody listens.
+fn read_device(mut device: Device, sender: &Sender<ExternalInput>) -> io::Result<()> {
+    loop {
+        for event in device.fetch_events()? {
+            let instant = instant_of(event.timestamp());
+            for (binding, state) in translate(&event) {
+                if sender.send(ExternalInput { binding, state, instant }).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+// Presses and releases described by a device event. Key events carry 1 for a press, 0 for a release and
+// 2 for an auto-repeat, and the wheel reports REL_WHEEL with the number of ticks.
+fn translate(event: &DeviceEvent) -> Vec<(Binding, ButtonState)> {
+    match event.event_type() {
+        EventType::KEY => {
+            let state = match event.value() {
+                0 => ButtonState::Released,
+                1 => ButtonState::Pressed,
+                _ => return Vec::new(),
+            };
+            binding_from_linux_code(event.code())
+                .map(|binding| (binding, state))
+                .into_iter()
+                .collect()
+        }
+        EventType::RELATIVE if event.code() == RelativeAxisType::REL_WHEEL.0 => {
+            let binding = match event.value() {
+                value if value > 0 => Binding::ScrollUp,
+                value if value < 0 => Binding::ScrollDown,
+                _ => return Vec::new(),
+            };
+            vec![(binding, ButtonState::Pressed), (binding, ButtonState::Released)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// The kernel stamps events with the wall clock; convert them to the monotonic clock used by `ExternalInput`.
+fn instant_of(timestamp: SystemTime) -> Instant {
+    let now = Instant::now();
+    let age = SystemTime::now().duration_since(timestamp).unwrap_or_default();
+    now.checked_sub(age).unwrap_or(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: Key, value: i32) -> DeviceEvent {
+        DeviceEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    fn wheel(value: i32) -> DeviceEvent {
+        DeviceEvent::new(EventType::RELATIVE, RelativeAxisType::REL_WHEEL.0, value)
+    }
+
+    #[test]
+    fn key_values_press_release_and_repeat() {
+        let q = Binding::Key(KeyCode::KeyQ);
+        assert_eq!(translate(&key(Key::KEY_Q, 1)), [(q, ButtonState::Pressed)]);
+        assert_eq!(translate(&key(Key::KEY_Q, 0)), [(q, ButtonState::Released)]);
+        assert_eq!(translate(&key(Key::KEY_Q, 2)), []);
+        assert_eq!(translate(&key(Key::BTN_SIDE, 1)), [(Binding::Mouse(MouseButton::Back), ButtonState::Pressed)]);
+        assert_eq!(translate(&key(Key::KEY_F24, 0)), [(Binding::Key(KeyCode::F24), ButtonState::Released)]);
+    }
+
+    #[test]
+    fn wheel_ticks_press_and_release() {
+        let tick = |binding| [(binding, ButtonState::Pressed), (binding, ButtonState::Released)];
+        assert_eq!(translate(&wheel(1)), tick(Binding::ScrollUp));
+        assert_eq!(translate(&wheel(-1)), tick(Binding::ScrollDown));
+        assert_eq!(translate(&wheel(0)), []);
+        assert_eq!(translate(&DeviceEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, 1)), []);
+    }
+}
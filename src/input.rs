@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::input::keyboard::{KeyboardFocusLost, KeyboardInput};
+use bevy::input::mouse::{MouseButtonInput, MouseWheel};
+use bevy::input::gamepad::GamepadButtonChangedEvent;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::binding::{Binding, TRIGGERS};
+use crate::config::Config;
+
+// A press or release of a binding, timed on the virtual clock (`Time::elapsed`).
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct InputEvent {
+    pub binding: Binding,
+    pub state: ButtonState,
+    pub time: Duration,
+}
+
+// Anything that produces presses and releases for the overlay: the window, a replay, a script or an external feed.
+pub trait InputSource: Send + Sync + 'static {
+    // Append the events that happened since the last poll to `events`, in the order they happened.
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>);
+}
+
+// Resource holding the input sources polled every frame.
+#[derive(Resource, Default)]
+pub struct InputSources(Vec<Box<dyn InputSource>>);
+
+impl InputSources {
+    pub fn push(&mut self, source: impl InputSource) {
+        self.0.push(Box::new(source));
+    }
+}
+
+// System to poll every input source, apply the events to `ButtonInput<Binding>` and publish them as `InputEvent`s.
+pub fn poll_input_sources(world: &mut World) {
+    world.resource_mut::<ButtonInput<Binding>>().clear();
+
+    let mut events = Vec::new();
+    world.resource_scope(|world, mut sources: Mut<InputSources>| {
+        for source in sources.0.iter_mut() {
+            source.poll(world, &mut events);
+        }
+    });
+
+    let mut bindings = world.resource_mut::<ButtonInput<Binding>>();
+    for event in &events {
+        match event.state {
+            ButtonState::Pressed => bindings.press(event.binding),
+            ButtonState::Released => bindings.release(event.binding),
+        }
+    }
+
+    world.send_event_batch(events);
+}
+
+// Input received by the overlay's window: keyboard, mouse buttons, scroll wheel and gamepads.
+// Keys and mouse buttons are read from the window's events rather than `ButtonInput`, so a key pressed
+// and released within a frame, or pressed twice between frames, still registers every press.
+pub struct WindowInput {
+    keyboard_and_mouse: bool,
+    keyboard_reader: ManualEventReader<KeyboardInput>,
+    mouse_reader: ManualEventReader<MouseButtonInput>,
+    focus_lost_reader: ManualEventReader<KeyboardFocusLost>,
+    held: HashSet<Binding>,
+    wheel_reader: ManualEventReader<MouseWheel>,
+    scrolled: Vec<Binding>,
+    trigger_reader: ManualEventReader<GamepadButtonChangedEvent>,
+    trigger_values: HashMap<GamepadButton, f32>,
+    gamepad_held: HashSet<Binding>,
+}
+
+impl Default for WindowInput {
+    fn default() -> Self {
+        Self {
+            keyboard_and_mouse: true,
+            keyboard_reader: ManualEventReader::default(),
+            mouse_reader: ManualEventReader::default(),
+            focus_lost_reader: ManualEventReader::default(),
+            held: HashSet::new(),
+            wheel_reader: ManualEventReader::default(),
+            scrolled: Vec::new(),
+            trigger_reader: ManualEventReader::default(),
+            trigger_values: HashMap::new(),
+            gamepad_held: HashSet::new(),
+        }
+    }
+}
+
+impl WindowInput {
+    // Only read gamepads, for when a global capture backend already reports the keyboard and mouse.
+    // Gamepads are seen whether or not the window has focus.
+    pub fn gamepads_only() -> Self {
+        Self {
+            keyboard_and_mouse: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl InputSource for WindowInput {
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>) {
+        let time = world.resource::<Time>().elapsed();
+        let mut push = |binding, state| events.push(InputEvent { binding, state, time });
+
+        if self.keyboard_and_mouse {
+            let keys = self
+                .keyboard_reader
+                .read(world.resource::<Events<KeyboardInput>>())
+                .map(|event| (Binding::Key(event.key_code), event.state));
+            let buttons = self
+                .mouse_reader
+                .read(world.resource::<Events<MouseButtonInput>>())
+                .map(|event| (Binding::Mouse(event.button), event.state));
+            for (binding, state) in keys.chain(buttons) {
+                // Held keys send presses again as they repeat, and those aren't new presses.
+                let changed = match state {
+                    ButtonState::Pressed => self.held.insert(binding),
+                    ButtonState::Released => self.held.remove(&binding),
+                };
+                if changed {
+                    push(binding, state);
+                }
+            }
+
+            // Keys released while another window has focus never send a release, so release everything.
+            if self.focus_lost_reader.read(world.resource::<Events<KeyboardFocusLost>>()).next().is_some() {
+                let keys: Vec<Binding> = self.held.iter().copied().filter(|binding| matches!(binding, Binding::Key(_))).collect();
+                for binding in keys {
+                    self.held.remove(&binding);
+                    push(binding, ButtonState::Released);
+                }
+            }
+
+            // Scroll ticks are released on the next frame, or right before another tick in the same direction.
+            for binding in self.scrolled.drain(..) {
+                push(binding, ButtonState::Released);
+            }
+            for event in self.wheel_reader.read(world.resource::<Events<MouseWheel>>()) {
+                let binding = match event.y {
+                    y if y > 0.0 => Binding::ScrollUp,
+                    y if y < 0.0 => Binding::ScrollDown,
+                    _ => continue,
+                };
+                if self.scrolled.contains(&binding) {
+                    push(binding, ButtonState::Released);
+                } else {
+                    self.scrolled.push(binding);
+                }
+                push(binding, ButtonState::Pressed);
+            }
+        }
+
+        // Triggers are read from their analog value, so the press threshold is configurable.
+        // The values come from the button events, which are sent for real and synthetic gamepads alike.
+        let gamepads = world.resource::<Gamepads>();
+        for event in self.trigger_reader.read(world.resource::<Events<GamepadButtonChangedEvent>>()) {
+            if TRIGGERS.contains(&event.button_type) {
+                self.trigger_values.insert(GamepadButton::new(event.gamepad, event.button_type), event.value);
+            }
+        }
+        self.trigger_values.retain(|button, _| gamepads.contains(button.gamepad));
+
+        // Collect the gamepad buttons held right now, both for their gamepad and for "any gamepad".
+        let gamepad_buttons = world.resource::<ButtonInput<GamepadButton>>();
+        let trigger_threshold = world.resource::<Config>().gamepad.trigger_threshold;
+        let mut held = HashSet::new();
+        for gamepad in gamepads.iter() {
+            let buttons = gamepad_buttons
+                .get_pressed()
+                .filter(|button| button.gamepad == gamepad && !TRIGGERS.contains(&button.button_type))
+                .map(|button| button.button_type);
+
+            let triggers = TRIGGERS.into_iter().filter(|&trigger| {
+                let value = self.trigger_values.get(&GamepadButton::new(gamepad, trigger)).copied().unwrap_or(0.0);
+                value >= trigger_threshold
+            });
+
+            for button in buttons.chain(triggers) {
+                held.insert(Binding::GamepadButton { gamepad: Some(gamepad.id), button });
+                held.insert(Binding::GamepadButton { gamepad: None, button });
+            }
+        }
+
+        // Release the gamepad bindings no gamepad holds anymore and press the newly held ones.
+        for &binding in self.gamepad_held.difference(&held) {
+            push(binding, ButtonState::Released);
+        }
+        for &binding in held.difference(&self.gamepad_held) {
+            push(binding, ButtonState::Pressed);
+        }
+        self.gamepad_held = held;
+    }
+}
+
+// Wraps a source so that it only reports the bindings that aren't keys of the layout.
+// A replay drives the keys during playback while live input keeps driving the hotkeys through this.
+pub struct HotkeysOnly<S> {
+    source: S,
+    polled: Vec<InputEvent>,
+}
+
+impl<S: InputSource> HotkeysOnly<S> {
+    pub fn new(source: S) -> Self {
+        Self { source, polled: Vec::new() }
+    }
+}
+
+impl<S: InputSource> InputSource for HotkeysOnly<S> {
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>) {
+        self.source.poll(world, &mut self.polled);
+        let keys = &world.resource::<Config>().keys;
+        events.extend(self.polled.drain(..).filter(|event| !keys.iter().any(|key| key.code == event.binding)));
+    }
+}
+
+// A fixed list of events played back on the virtual clock, for tests and demos.
+pub struct ScriptedSource {
+    events: Vec<InputEvent>,
+    next_event: usize,
+}
+
+impl ScriptedSource {
+    // Create a source emitting `events` once the virtual clock reaches their time.
+    pub fn new(mut events: Vec<InputEvent>) -> Self {
+        events.sort_by_key(|event| event.time);
+        Self { events, next_event: 0 }
+    }
+}
+
+impl InputSource for ScriptedSource {
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>) {
+        let now = world.resource::<Time>().elapsed();
+        while let Some(event) = self.events.get(self.next_event) {
+            if event.time > now {
+                break;
+            }
+            events.push(*event);
+            self.next_event += 1;
+        }
+    }
+}
+
+// A press or release sent to a `ChannelSource` from another thread, timed with the system's monotonic clock.
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalInput {
+    pub binding: Binding,
+    pub state: ButtonState,
+    pub instant: Instant,
+}
+
+// Input fed from outside the app through a channel, e.g. by a global key capture backend.
+// Senders report the same presses `WindowInput` does: a held key is pressed once however long it repeats,
+// and each scroll tick is a press followed right away by a release.
+pub struct ChannelSource {
+    receiver: Receiver<ExternalInput>,
+}
+
+impl ChannelSource {
+    // Create a source together with the sender that feeds it.
+    pub fn new() -> (Self, Sender<ExternalInput>) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        (Self { receiver }, sender)
+    }
+}
+
+impl InputSource for ChannelSource {
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>) {
+        // Map the sender's timestamps onto the virtual clock by how long ago they happened.
+        let now = world.resource::<Time>().elapsed();
+        let received_at = Instant::now();
+        for input in self.receiver.try_iter() {
+            events.push(InputEvent {
+                binding: input.binding,
+                state: input.state,
+                time: now.saturating_sub(received_at.saturating_duration_since(input.instant)),
+            });
+        }
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::ecs::system::EntityCommands;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::sprite::{Anchor, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::PrimaryWindow;
+
+use crate::binding::Binding;
+use crate::config::{Config, LayoutConfig, TrailDirection};
+use crate::input::InputEvent;
+use crate::persistence::ClickStore;
+use crate::stats::{spawn_stats_text, KeyKpsText, StatsText};
+
+// Size of a key the key texts are laid out for; texts of keys of another configured size are scaled to match.
+pub const KEY_SIZE: f32 = 80.0;
+
+// Define the initial room for the trails past the keys, along the trails (its height when they move up or down).
+const TRAIL_LENGTH: f32 = 520.0;
+
+// Calculate the rectangle of each configured key in pixels at the initial window size, in configuration order.
+// X runs across the trails and Y away from them, from the corner of the block of keys nearest to the trails.
+fn key_rects(config: &Config) -> Vec<Rect> {
+    let pitch = config.layout.key_size + config.layout.key_spacing;
+    let mut row_ends: HashMap<usize, f32> = HashMap::new();
+    let mut rects: Vec<Rect> = config
+        .keys
+        .iter()
+        .map(|key| {
+            // Keys follow the previous key of their row, after any offset of their own.
+            let row_end = row_ends.entry(key.row).or_default();
+            let start = Vec2::new(*row_end + key.x.unwrap_or(0.0), key.row as f32 + key.y.unwrap_or(0.0));
+            let size = Vec2::new(key.width.unwrap_or(1.0), key.height.unwrap_or(1.0));
+            *row_end = start.x + size.x;
+            Rect::from_corners(start * pitch, (start + size) * pitch - config.layout.key_spacing)
+        })
+        .collect();
+
+    // Move the block to the origin, whatever offsets the keys were given.
+    let min = rects.iter().fold(Vec2::INFINITY, |min, rect| min.min(rect.min));
+    for rect in &mut rects {
+        rect.min -= min;
+        rect.max -= min;
+    }
+    rects
+}
+
+// Calculate the size of the block of keys, across and along the trails.
+fn block_size(rects: &[Rect]) -> Vec2 {
+    rects.iter().fold(Vec2::ZERO, |max, rect| max.max(rect.max))
+}
+
+// Calculate the window size across and along the trails that comfortably fits a block of keys and room for the trails.
+fn fitted_size(block: Vec2, key_spacing: f32) -> Vec2 {
+    Vec2::new(block.x + key_spacing * 2.0, block.y + TRAIL_LENGTH)
+}
+
+// Calculate the initial window size for the configured keys and trail direction.
+pub fn window_size(config: &Config) -> Vec2 {
+    let size = fitted_size(block_size(&key_rects(config)), config.layout.key_spacing);
+    if config.layout.direction.is_vertical() { size } else { size.yx() }
+}
+
+// Placement of the keys in a window of a given size.
+struct Layout {
+    direction: TrailDirection,
+    // Rectangles of the keys and the size of their block at the initial window size, see `key_rects`.
+    keys: Vec<Rect>,
+    block: Vec2,
+    // Window extent along the trails.
+    length: f32,
+    // Scale of the keys, so the block fits the window.
+    // Keys keep their size in a window of the initial size, and grow or shrink with it.
+    scale: f32,
+}
+
+impl Layout {
+    // Lay out the configured keys in the window.
+    fn of(config: &Config, window: &Window) -> Self {
+        let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+        Self::new(config, window_size)
+    }
+
+    fn new(config: &Config, window_size: Vec2) -> Self {
+        let direction = config.layout.direction;
+        let (width, length) = if direction.is_vertical() {
+            (window_size.x, window_size.y)
+        } else {
+            (window_size.y, window_size.x)
+        };
+        let keys = key_rects(config);
+        let block = block_size(&keys);
+        let fitted = fitted_size(block, config.layout.key_spacing);
+        Self {
+            direction,
+            keys,
+            block,
+            length,
+            scale: (width / fitted.x).min(length / fitted.y),
+        }
+    }
+
+    // Calculate the center of a key, with the block centered on the edge of the window the trails move away from.
+    fn key_position(&self, index: usize) -> Vec2 {
+        let center = self.keys[index].center();
+        let across = (center.x - self.block.x / 2.0) * self.scale;
+        let along = -self.length / 2.0 + (self.block.y - center.y) * self.scale;
+        self.direction.across() * across + self.direction.vector() * along
+    }
+
+    // Calculate the size of a key along the window's axes, before scaling.
+    fn key_size(&self, index: usize) -> Vec2 {
+        let size = self.keys[index].size();
+        self.direction.across().abs() * size.x + self.direction.vector().abs() * size.y
+    }
+
+    // Calculate where the trails of a key start and how wide they are.
+    fn trail_start(&self, index: usize) -> TrailStart {
+        let size = self.keys[index].size() * self.scale;
+        TrailStart {
+            origin: self.key_position(index) + self.direction.vector() * size.y / 2.0, // On the edge of the key they move towards
+            width: size.x,
+        }
+    }
+
+    // Calculate the position of the KPS and BPM readout, just past the keys.
+    fn stats_position(&self) -> Vec2 {
+        self.direction.vector() * (-self.length / 2.0 + self.block.y * self.scale + 16.0)
+    }
+
+    // Anchor of the readout, so sideways trails don't push half of it over the keys.
+    fn stats_anchor(&self) -> Anchor {
+        match self.direction {
+            TrailDirection::Up | TrailDirection::Down => Anchor::Center,
+            TrailDirection::Left => Anchor::CenterRight,
+            TrailDirection::Right => Anchor::CenterLeft,
+        }
+    }
+
+    // Distance from the center of the window to the edge the trails leave through.
+    fn trail_bound(&self) -> f32 {
+        self.length / 2.0
+    }
+}
+
+// Component to uniquely identify each key by its binding.
+#[derive(Component)]
+pub struct KeyID(pub Binding);
+
+// Component to represent a trail left by a key press, described by when its key was pressed and released.
+// Its far end left the key when the key was pressed and its near end when it was released, both moving at
+// the configured trail speed, so its length is the hold duration whatever the frame rate.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Trail {
+    pub key: Binding,
+    // Where the trail starts, on the edge of its key.
+    pub origin: Vec2,
+    // Times of the press and release on the virtual clock, like `InputEvent::time`.
+    pub pressed_at: Duration,
+    pub released_at: Option<Duration>,
+}
+
+impl Trail {
+    // Create the trail of a press of a key whose trails start at `start`.
+    pub fn new(key: Binding, start: &TrailStart, pressed_at: Duration) -> Self {
+        Self {
+            key,
+            origin: start.origin,
+            pressed_at,
+            released_at: None,
+        }
+    }
+
+    // Whether the trail's key is still held.
+    pub fn is_active(&self) -> bool {
+        self.released_at.is_none()
+    }
+
+    // Distances from the origin to the near and far ends of the trail at `now`.
+    fn ends(&self, speed: f32, now: Duration) -> (f32, f32) {
+        let distance = |since: Duration| now.saturating_sub(since).as_secs_f32() * speed;
+        (self.released_at.map_or(0.0, distance), distance(self.pressed_at))
+    }
+
+    // Move and stretch the trail's transform to where it is at `now`.
+    // The trail's length is its local Y scale, which was turned towards the trail direction.
+    pub fn place(&self, transform: &mut Transform, layout: &LayoutConfig, now: Duration) {
+        let direction = layout.direction.vector();
+        let (near, far) = self.ends(layout.trail_speed, now);
+        transform.translation = (self.origin + direction * (near + far) / 2.0).extend(transform.translation.z);
+        transform.scale.y = (far - near).max(1.0); // Keep very short presses visible
+    }
+}
+
+// Event sent when a key of the layout is pressed, after its click count was updated.
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct KeyPressed {
+    // The key's entity, holding its `KeyID` and `ClicksCount` text.
+    pub key: Entity,
+    pub binding: Binding,
+    // Time of the press on the virtual clock, like `InputEvent::time`.
+    pub time: Duration,
+}
+
+// Event sent when a key of the layout is released, with how long it was held.
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct KeyReleased {
+    pub key: Entity,
+    pub binding: Binding,
+    pub time: Duration,
+    pub held: Duration,
+}
+
+// Component to keep track of the number of times a key is pressed.
+#[derive(Component)]
+pub struct ClicksCount(pub usize);
+
+// Resource holding the mesh every trail is drawn with, a unit square stretched to the trail's width and length.
+// Sharing it keeps the mesh assets from growing with every press.
+#[derive(Resource)]
+pub struct TrailMesh(pub Mesh2dHandle);
+
+impl FromWorld for TrailMesh {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        Self(Mesh2dHandle(meshes.add(Rectangle::new(1.0, 1.0))))
+    }
+}
+
+// Component holding the material shared by the trails of a key, created along with the key.
+#[derive(Component)]
+pub struct TrailMaterial(pub Handle<ColorMaterial>);
+
+// Component holding where the trails of a key start and how wide they are, kept up to date with the window size.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TrailStart {
+    pub origin: Vec2,
+    pub width: f32,
+}
+
+// System to spawn the 2D camera the keys are shown with.
+pub fn spawn_camera(mut commands: Commands) {
+    // Spawn a 2D camera with tonemapping to handle rendering.
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                ..Default::default()
+            },
+            tonemapping: Tonemapping::TonyMcMapface,
+            transform: Transform::from_xyz(0.0, 0.0, 1.0), // Position the camera in the center
+            ..Default::default()
+        },
+    ));
+}
+
+// System to set up the initial scene, including the keys and UI elements.
+pub fn setup_graphics(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<Config>,
+    store: Res<ClickStore>,
+) {
+    let layout = Layout::of(&config, windows.single());
+
+    // Show the lifetime click counts saved by previous sessions.
+    spawn_keys(&mut commands, &mut meshes, &mut materials, &config, &layout, store.totals());
+
+    // Spawn the KPS and BPM readout just past the keys.
+    spawn_stats_text(&mut commands, layout.stats_position(), layout.stats_anchor());
+}
+
+// System to rebuild the keys and resize the window to fit them after the configuration file was reloaded.
+// Trails are cleared, since their keys may have moved or changed direction.
+#[allow(clippy::too_many_arguments)]
+pub fn rebuild_keys(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    config: Res<Config>,
+    store: Res<ClickStore>,
+    key_query: Query<Entity, With<KeyID>>,
+    trail_query: Query<Entity, With<Trail>>,
+) {
+    for entity in key_query.iter().chain(trail_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Resize the window to the new layout, later manual resizes are handled by `relayout_keys`.
+    let mut window = windows.single_mut();
+    let size = window_size(&config);
+    window.resolution.set(size.x, size.y);
+
+    // The click counts are restored from the store, so they survive the rebuild.
+    let layout = Layout::of(&config, &window);
+    spawn_keys(&mut commands, &mut meshes, &mut materials, &config, &layout, store.totals());
+}
+
+// System to reposition and rescale the keys, their trails and the readout to fit the window.
+// Runs after the window was resized, and after a reload once `rebuild_keys` resized the window to the new layout.
+#[allow(clippy::type_complexity)]
+pub fn relayout_keys(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<Config>,
+    mut key_query: Query<(&KeyID, &mut Transform, &mut TrailStart)>,
+    mut trail_query: Query<(&mut Trail, &mut Transform), Without<KeyID>>,
+    mut stats_query: Query<(&mut Transform, &mut Anchor), (With<StatsText>, Without<KeyID>, Without<Trail>)>,
+) {
+    let layout = Layout::of(&config, windows.single());
+
+    for (key_id, mut transform, mut trail_start) in key_query.iter_mut() {
+        if let Some(index) = config.keys.iter().position(|key| key.code == key_id.0) {
+            transform.translation = layout.key_position(index).extend(0.0);
+            transform.scale = Vec3::splat(layout.scale);
+            *trail_start = layout.trail_start(index);
+        }
+    }
+
+    // Trails keep their length and move with their key; `update_trails` places them from their new origin.
+    for (mut trail, mut transform) in trail_query.iter_mut() {
+        if let Some(index) = config.keys.iter().position(|key| key.code == trail.key) {
+            let start = layout.trail_start(index);
+            trail.origin = start.origin;
+            transform.scale.x = start.width;
+        }
+    }
+
+    for (mut transform, mut anchor) in stats_query.iter_mut() {
+        transform.translation = layout.stats_position().extend(transform.translation.z);
+        *anchor = layout.stats_anchor();
+    }
+}
+
+// Spawn the keys described by the configuration, restoring any known click counts.
+fn spawn_keys(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    config: &Config,
+    layout: &Layout,
+    counts: &HashMap<Binding, usize>,
+) {
+    // Prepare the positions and properties for each key based on the configuration.
+    let mut keys = Vec::new();
+    for (i, key) in config.keys.iter().enumerate() {
+        keys.push((key.code, key.color, key.label(), layout.key_position(i), layout.key_size(i), layout.trail_start(i)));
+    }
+
+    // Texts are laid out for keys of `KEY_SIZE`, and follow the configured size.
+    let text_scale = config.layout.key_size / KEY_SIZE;
+    let text_transform = |y: f32| Transform::from_translation(Vec3::new(0.0, y * text_scale, Vec3::Z.z)).with_scale(Vec3::splat(text_scale));
+
+    // Spawn the keys with their respective materials and positions.
+    for (binding, color, label, position, size, trail_start) in keys {
+        let clicks = counts.get(&binding).copied().unwrap_or(0);
+
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(size))), // Create a rectangle mesh of the key's size
+                material: materials.add(ColorMaterial::from(color)), // Set the color of the key
+                transform: Transform::from_translation(position.extend(0.0)).with_scale(Vec3::splat(layout.scale)), // Position and scale the key in 2D space
+                ..Default::default()
+            },
+            KeyID(binding), // Assign the KeyID component to identify the key
+            TrailMaterial(materials.add(Color::WHITE)), // White trails, shared by every press of the key
+            trail_start,
+        )).with_children(|parent| {
+            // Add a text child to display the key's label (e.g., Q, W, C) on the key.
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(
+                    label, // Configured label, or a short name of the binding
+                    TextStyle {
+                        font: Default::default(),
+                        font_size: 32.0, // Large font for key code
+                        color: Color::WHITE,
+                    },
+                ),
+                transform: text_transform(0.0), // Center the text on the key
+                ..Default::default()
+            });
+        }).with_children(|parent| {
+            // Add a second text child to display the number of times the key is pressed.
+            parent.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        clicks.to_string(), // Initial click count
+                        TextStyle {
+                            font: Default::default(),
+                            font_size: 16.0, // Smaller font for click count
+                            color: Color::WHITE,
+                        },
+                    ).with_justify(JustifyText::Center),
+                    transform: text_transform(-26.0), // Position below the key code text
+                    ..Default::default()
+                },
+                ClicksCount(clicks), // Initialize click count to the saved lifetime total
+            ));
+        }).with_children(|parent| {
+            // Add a third text child to display the key's presses per second.
+            parent.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "0", // Initial KPS
+                        TextStyle {
+                            font: Default::default(),
+                            font_size: 16.0, // Smaller font for KPS
+                            color: Color::WHITE,
+                        },
+                    ).with_justify(JustifyText::Center),
+                    transform: text_transform(26.0), // Position above the key code text
+                    ..Default::default()
+                },
+                KeyKpsText,
+            ));
+        });
+    }
+}
+
+// System to handle key presses, update visual states, and spawn trails.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_keys(
+    time: Res<Time>,
+    input: Res<ButtonInput<Binding>>,
+    mut input_events: EventReader<InputEvent>,
+    mut pressed_events: EventWriter<KeyPressed>,
+    mut released_events: EventWriter<KeyReleased>,
+    mut pressed_at: Local<HashMap<Binding, Duration>>,
+    mut store: ResMut<ClickStore>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    trail_mesh: Res<TrailMesh>,
+    config: Res<Config>,
+    key_query: Query<(Entity, &KeyID, &Handle<ColorMaterial>, &TrailMaterial, &TrailStart, &Children)>,
+    mut text_query: Query<(&mut Text, &mut ClicksCount)>,
+) {
+    let mut key_presses = Vec::new(); // Store registered key presses
+
+    // Iterate over all keys to update their visual state.
+    for (_, key_id, material_handle, ..) in key_query.iter() {
+        // Modify the key's appearance based on its press state.
+        if let Some(material) = materials.get_mut(material_handle) {
+            if input.pressed(key_id.0) {
+                material.color.set_alpha(0.5); // Semi-transparent when pressed
+            } else {
+                material.color.set_alpha(1.0); // Full opacity when released
+            }
+        }
+    }
+
+    // Register every press and release of a key in the layout, update the click count display and tell other systems.
+    for event in input_events.read() {
+        let Some((key, key_id, _, trail_material, trail_start, children)) = key_query.iter().find(|(_, key_id, ..)| key_id.0 == event.binding) else {
+            continue;
+        };
+
+        match event.state {
+            ButtonState::Pressed => {
+                key_presses.push((Trail::new(key_id.0, trail_start, event.time), trail_start.width, trail_material)); // Store the press's trail, its width and material
+                pressed_events.send(KeyPressed { key, binding: key_id.0, time: event.time });
+                pressed_at.insert(key_id.0, event.time);
+                let total = store.record(key_id.0); // Increment the lifetime and session click counts
+
+                for &child in children.iter() {
+                    if let Ok((mut text, mut clicks_count)) = text_query.get_mut(child) {
+                        clicks_count.0 = total; // Show the new lifetime click count
+                        text.sections[0].value = clicks_count.0.to_string(); // Update the displayed click count
+                    }
+                }
+            }
+            ButtonState::Released => {
+                // A key released in the same frame it was pressed leaves a trail as long as it was held.
+                if let Some((trail, ..)) = key_presses.iter_mut().rev().find(|(trail, ..)| trail.key == event.binding) {
+                    trail.released_at = Some(event.time);
+                }
+                // Keys already held when the overlay started have no known press to measure from.
+                if let Some(pressed_at) = pressed_at.remove(&key_id.0) {
+                    released_events.send(KeyReleased {
+                        key,
+                        binding: key_id.0,
+                        time: event.time,
+                        held: event.time.saturating_sub(pressed_at),
+                    });
+                }
+            }
+        }
+    }
+
+    // Spawn a trail for each registered key press.
+    for (trail, width, trail_material) in key_presses {
+        spawn_trail(&mut commands, &trail_mesh, trail_material, trail, width, &config.layout, time.elapsed());
+    }
+}
+
+// Spawn a trail at where it is at `now`, its length pointing the way trails move.
+// Trails only hold handles to the shared mesh and the key's material, so pressing keys allocates no assets.
+pub fn spawn_trail<'a>(
+    commands: &'a mut Commands,
+    mesh: &TrailMesh,
+    material: &TrailMaterial,
+    trail: Trail,
+    width: f32,
+    layout: &LayoutConfig,
+    now: Duration,
+) -> EntityCommands<'a> {
+    let mut transform = Transform {
+        translation: Vec3::NEG_Z, // Behind the keys, so trails from the back rows pass under the rows ahead
+        rotation: Quat::from_rotation_z(Vec2::Y.angle_between(layout.direction.vector())), // Turn the trail's length towards its direction
+        scale: Vec3::new(width, 1.0, 1.0),
+    };
+    trail.place(&mut transform, layout, now);
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.0.clone(),
+            material: material.0.clone(),
+            transform,
+            ..Default::default()
+        },
+        trail,
+    ))
+}
+
+// System to update the trails, moving them away from their keys and despawning them when they exit the window.
+// Each trail is placed from its press and release times, so trails end exactly where their timing says.
+pub fn update_trails(
+    time: Res<Time>,
+    mut input_events: EventReader<InputEvent>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    config: Res<Config>,
+    mut commands: Commands,
+    mut trail_query: Query<(Entity, &mut Transform, &mut Trail)>,
+) {
+    // Trails leave through the edge of the window they move towards, wherever it is after a resize.
+    let direction = config.layout.direction.vector();
+    let bound = Layout::of(&config, windows.single()).trail_bound();
+    let now = time.elapsed();
+
+    // Release the held trail of each released key at the time of the release.
+    for event in input_events.read().filter(|event| event.state == ButtonState::Released) {
+        if let Some((.., mut trail)) = trail_query.iter_mut().find(|(.., trail)| trail.key == event.binding && trail.is_active()) {
+            trail.released_at = Some(event.time);
+        }
+    }
+
+    for (entity, mut trail_transform, trail) in trail_query.iter_mut() {
+        trail.place(&mut trail_transform, &config.layout, now);
+
+        // De-spawn the trail once its near end has moved out of the window's bounds.
+        let (near, _) = trail.ends(config.layout.trail_speed, now);
+        if !trail.is_active() && trail.origin.dot(direction) + near > bound {
+            commands.entity(entity).despawn();
+        }
+    }
+}
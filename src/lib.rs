@@ -0,0 +1,19 @@
+pub mod binding;
+pub mod config;
+#[cfg(feature = "evdev")]
+pub mod evdev_input;
+pub mod input;
+pub mod keys;
+pub mod nohboard;
+pub mod overlay;
+pub mod persistence;
+pub mod plugin;
+pub mod replay;
+#[cfg(any(feature = "evdev", feature = "x11"))]
+mod scancode;
+pub mod sound;
+pub mod stats;
+#[cfg(feature = "x11")]
+pub mod x11_input;
+
+pub use plugin::ClickyPlugin;
@@ -0,0 +1,411 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::binding::Binding;
+use crate::config::KeyConfig;
+
+// Color of imported keys, since NohBoard keeps colors in separate style files.
+const KEY_COLOR: Color = Color::WHITE;
+
+// Windows virtual-key codes, which NohBoard uses for keyboard keys, and the keys they stand for.
+const KEYS: [(u32, KeyCode); 120] = [
+    (0x08, KeyCode::Backspace),
+    (0x09, KeyCode::Tab),
+    (0x0D, KeyCode::Enter),
+    (0x10, KeyCode::ShiftLeft),
+    (0x11, KeyCode::ControlLeft),
+    (0x12, KeyCode::AltLeft),
+    (0x13, KeyCode::Pause),
+    (0x14, KeyCode::CapsLock),
+    (0x1B, KeyCode::Escape),
+    (0x20, KeyCode::Space),
+    (0x21, KeyCode::PageUp),
+    (0x22, KeyCode::PageDown),
+    (0x23, KeyCode::End),
+    (0x24, KeyCode::Home),
+    (0x25, KeyCode::ArrowLeft),
+    (0x26, KeyCode::ArrowUp),
+    (0x27, KeyCode::ArrowRight),
+    (0x28, KeyCode::ArrowDown),
+    (0x2C, KeyCode::PrintScreen),
+    (0x2D, KeyCode::Insert),
+    (0x2E, KeyCode::Delete),
+    (0x30, KeyCode::Digit0),
+    (0x31, KeyCode::Digit1),
+    (0x32, KeyCode::Digit2),
+    (0x33, KeyCode::Digit3),
+    (0x34, KeyCode::Digit4),
+    (0x35, KeyCode::Digit5),
+    (0x36, KeyCode::Digit6),
+    (0x37, KeyCode::Digit7),
+    (0x38, KeyCode::Digit8),
+    (0x39, KeyCode::Digit9),
+    (0x41, KeyCode::KeyA),
+    (0x42, KeyCode::KeyB),
+    (0x43, KeyCode::KeyC),
+    (0x44, KeyCode::KeyD),
+    (0x45, KeyCode::KeyE),
+    (0x46, KeyCode::KeyF),
+    (0x47, KeyCode::KeyG),
+    (0x48, KeyCode::KeyH),
+    (0x49, KeyCode::KeyI),
+    (0x4A, KeyCode::KeyJ),
+    (0x4B, KeyCode::KeyK),
+    (0x4C, KeyCode::KeyL),
+    (0x4D, KeyCode::KeyM),
+    (0x4E, KeyCode::KeyN),
+    (0x4F, KeyCode::KeyO),
+    (0x50, KeyCode::KeyP),
+    (0x51, KeyCode::KeyQ),
+    (0x52, KeyCode::KeyR),
+    (0x53, KeyCode::KeyS),
+    (0x54, KeyCode::KeyT),
+    (0x55, KeyCode::KeyU),
+    (0x56, KeyCode::KeyV),
+    (0x57, KeyCode::KeyW),
+    (0x58, KeyCode::KeyX),
+    (0x59, KeyCode::KeyY),
+    (0x5A, KeyCode::KeyZ),
+    (0x5B, KeyCode::SuperLeft),
+    (0x5C, KeyCode::SuperRight),
+    (0x5D, KeyCode::ContextMenu),
+    (0x60, KeyCode::Numpad0),
+    (0x61, KeyCode::Numpad1),
+    (0x62, KeyCode::Numpad2),
+    (0x63, KeyCode::Numpad3),
+    (0x64, KeyCode::Numpad4),
+    (0x65, KeyCode::Numpad5),
+    (0x66, KeyCode::Numpad6),
+    (0x67, KeyCode::Numpad7),
+    (0x68, KeyCode::Numpad8),
+    (0x69, KeyCode::Numpad9),
+    (0x6A, KeyCode::NumpadMultiply),
+    (0x6B, KeyCode::NumpadAdd),
+    (0x6C, KeyCode::NumpadComma),
+    (0x6D, KeyCode::NumpadSubtract),
+    (0x6E, KeyCode::NumpadDecimal),
+    (0x6F, KeyCode::NumpadDivide),
+    (0x70, KeyCode::F1),
+    (0x71, KeyCode::F2),
+    (0x72, KeyCode::F3),
+    (0x73, KeyCode::F4),
+    (0x74, KeyCode::F5),
+    (0x75, KeyCode::F6),
+    (0x76, KeyCode::F7),
+    (0x77, KeyCode::F8),
+    (0x78, KeyCode::F9),
+    (0x79, KeyCode::F10),
+    (0x7A, KeyCode::F11),
+    (0x7B, KeyCode::F12),
+    (0x7C, KeyCode::F13),
+    (0x7D, KeyCode::F14),
+    (0x7E, KeyCode::F15),
+    (0x7F, KeyCode::F16),
+    (0x80, KeyCode::F17),
+    (0x81, KeyCode::F18),
+    (0x82, KeyCode::F19),
+    (0x83, KeyCode::F20),
+    (0x84, KeyCode::F21),
+    (0x85, KeyCode::F22),
+    (0x86, KeyCode::F23),
+    (0x87, KeyCode::F24),
+    (0x90, KeyCode::NumLock),
+    (0x91, KeyCode::ScrollLock),
+    (0xA0, KeyCode::ShiftLeft),
+    (0xA1, KeyCode::ShiftRight),
+    (0xA2, KeyCode::ControlLeft),
+    (0xA3, KeyCode::ControlRight),
+    (0xA4, KeyCode::AltLeft),
+    (0xA5, KeyCode::AltRight),
+    (0xBA, KeyCode::Semicolon),
+    (0xBB, KeyCode::Equal),
+    (0xBC, KeyCode::Comma),
+    (0xBD, KeyCode::Minus),
+    (0xBE, KeyCode::Period),
+    (0xBF, KeyCode::Slash),
+    (0xC0, KeyCode::Backquote),
+    (0xDB, KeyCode::BracketLeft),
+    (0xDC, KeyCode::Backslash),
+    (0xDD, KeyCode::BracketRight),
+    (0xDE, KeyCode::Quote),
+    (0xE2, KeyCode::IntlBackslash),
+];
+
+// NohBoard's mouse button codes, in order.
+const MOUSE_BUTTONS: [MouseButton; 5] =
+    [MouseButton::Left, MouseButton::Middle, MouseButton::Right, MouseButton::Back, MouseButton::Forward];
+
+// A NohBoard keyboard definition, the `keyboard.json` file of a NohBoard keyboard.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Definition {
+    elements: Vec<Element>,
+}
+
+// Any element of a keyboard definition. Only keys have boundaries and key codes.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Element {
+    // Type names look like "KeyboardKeyDefinition:#ThoNohT.NohBoard.Keyboard.ElementDefinitions".
+    #[serde(rename = "__type")]
+    kind: String,
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    boundaries: Vec<Point>,
+    #[serde(default)]
+    key_codes: Vec<u32>,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+impl Element {
+    fn kind(&self) -> &str {
+        let kind = self.kind.split(':').next().unwrap_or_default();
+        kind.strip_suffix("Definition").unwrap_or(kind)
+    }
+
+    // Binding of each key code, or `None` for codes the overlay has no binding for.
+    fn bindings(&self) -> Vec<Option<Binding>> {
+        let binding = |code: u32| match self.kind() {
+            "KeyboardKey" => KEYS.iter().find(|(other, _)| *other == code).map(|(_, key_code)| Binding::Key(*key_code)),
+            "MouseKey" => MOUSE_BUTTONS.get(code as usize).map(|button| Binding::Mouse(*button)),
+            // Scrolling left and right has no binding
+            "MouseScroll" => [Binding::ScrollUp, Binding::ScrollDown].get(code as usize).copied(),
+            _ => None,
+        };
+        self.key_codes.iter().map(|&code| binding(code)).collect()
+    }
+
+    fn bounds(&self) -> Rect {
+        let empty = Rect { min: Vec2::INFINITY, max: Vec2::NEG_INFINITY };
+        self.boundaries.iter().fold(empty, |rect, point| rect.union_point(Vec2::new(point.x, point.y)))
+    }
+
+    // Whether the boundaries are the four corners of their bounding box.
+    fn is_rectangle(&self) -> bool {
+        let bounds = self.bounds();
+        self.boundaries.len() == 4
+            && self.boundaries.iter().all(|point| {
+                (point.x == bounds.min.x || point.x == bounds.max.x) && (point.y == bounds.min.y || point.y == bounds.max.y)
+            })
+    }
+
+    fn describe(&self) -> String {
+        match self.text.trim() {
+            "" => format!("element {} ({})", self.id, self.kind()),
+            text => format!("element {} ({} {text:?})", self.id, self.kind()),
+        }
+    }
+}
+
+// Keys converted from a NohBoard keyboard definition, and what could not be converted.
+pub struct Import {
+    pub keys: Vec<KeyConfig>,
+    // Key size and spacing in pixels that reproduce the definition's layout.
+    pub key_size: f32,
+    pub key_spacing: f32,
+    // One message per element that was skipped or only partly converted.
+    pub warnings: Vec<String>,
+}
+
+// Read and convert the NohBoard keyboard definition at `path`.
+pub fn import_file(path: &Path) -> Result<Import, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("could not read {}: {err}", path.display()))?;
+    import(&source).map_err(|err| format!("could not import {}: {err}", path.display()))
+}
+
+// Convert a NohBoard keyboard definition into keys of the overlay's layout.
+// Keys keep their position, size and text. Elements the overlay can't show, like mouse speed indicators
+// and keys without a known key code, are reported in the warnings.
+pub fn import(source: &str) -> Result<Import, String> {
+    // Other overlays' files, like KeyViz settings, hold no key layout and fail here.
+    let definition: Definition = serde_json::from_str(source)
+        .map_err(|err| format!("not a NohBoard keyboard definition ({err}), only NohBoard keyboard.json files can be imported"))?;
+
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::new();
+    let mut keys = Vec::new();
+    for element in &definition.elements {
+        if !matches!(element.kind(), "KeyboardKey" | "MouseKey" | "MouseScroll") {
+            warnings.push(format!("{} is not supported", element.describe()));
+            continue;
+        }
+        if element.boundaries.is_empty() {
+            warnings.push(format!("{} has no boundaries", element.describe()));
+            continue;
+        }
+
+        // The overlay shows one binding per key, so keys for several inputs keep the first one it knows.
+        let bindings = element.bindings();
+        let Some(binding) = bindings.iter().flatten().copied().find(|binding| !seen.contains(binding)) else {
+            match bindings.iter().flatten().next() {
+                Some(binding) => warnings.push(format!("{} duplicates the {binding} key", element.describe())),
+                None => warnings.push(format!("{} has no supported key code in {:?}", element.describe(), element.key_codes)),
+            }
+            continue;
+        };
+        if bindings.len() > 1 {
+            warnings.push(format!("{} has several key codes, only {binding} is kept", element.describe()));
+        }
+        if !element.is_rectangle() {
+            warnings.push(format!("{} is not a rectangle, its bounding box is used", element.describe()));
+        }
+        seen.insert(binding);
+        keys.push((binding, element.text.trim(), element.bounds()));
+    }
+
+    if keys.is_empty() {
+        return Err("the keyboard has no supported keys".to_string());
+    }
+    let key_size = most_common(keys.iter().map(|(_, _, bounds)| bounds.height()));
+    let rows = rows(keys.iter().map(|(_, _, bounds)| *bounds).collect(), key_size);
+    let key_spacing = most_common(rows.iter().flat_map(|row| {
+        row.windows(2).map(|pair| keys[pair[1]].2.min.x - keys[pair[0]].2.max.x).filter(|gap| *gap >= 0.0)
+    }));
+
+    // Place keys in key units, following the previous key of their row like the layout does.
+    let pitch = key_size + key_spacing;
+    let origin = keys.iter().fold(Vec2::INFINITY, |min, (_, _, bounds)| min.min(bounds.min));
+    let mut configs = Vec::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        let mut row_end = 0.0;
+        for &index in row {
+            let (binding, text, bounds) = keys[index];
+            let start = round((bounds.min - origin) / pitch);
+            let size = round((bounds.size() + key_spacing) / pitch);
+            let offset = round(Vec2::new(start.x - row_end, start.y - row_index as f32));
+            row_end = start.x + size.x;
+
+            let mut key = KeyConfig::new(binding, KEY_COLOR);
+            key.label = Some(text.to_string()).filter(|text| !text.is_empty() && *text != binding.default_label());
+            key.row = row_index;
+            key.x = Some(offset.x).filter(|x| *x != 0.0);
+            key.y = Some(offset.y).filter(|y| *y != 0.0);
+            key.width = Some(size.x).filter(|width| *width != 1.0);
+            key.height = Some(size.y).filter(|height| *height != 1.0);
+            configs.push(key);
+        }
+    }
+
+    Ok(Import {
+        keys: configs,
+        key_size,
+        key_spacing,
+        warnings,
+    })
+}
+
+// Group keys into rows by their top edge, top row first, each row sorted from left to right.
+fn rows(bounds: Vec<Rect>, key_size: f32) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_by(|a, b| bounds[*a].min.y.total_cmp(&bounds[*b].min.y));
+
+    let mut rows: Vec<Vec<usize>> = Vec::new();
+    let mut row_top = f32::NEG_INFINITY;
+    for index in order {
+        // Keys less than half a key below the row's first key are still on that row
+        if bounds[index].min.y - row_top < key_size / 2.0 {
+            rows.last_mut().expect("rows start with the first key").push(index);
+        } else {
+            row_top = bounds[index].min.y;
+            rows.push(vec![index]);
+        }
+    }
+    for row in &mut rows {
+        row.sort_by(|a, b| bounds[*a].min.x.total_cmp(&bounds[*b].min.x));
+    }
+    rows
+}
+
+// The most common of `values` rounded to whole pixels, the smallest on ties, or zero if there are none.
+fn most_common(values: impl Iterator<Item = f32>) -> f32 {
+    let mut counts: HashMap<i32, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value.round() as i32).or_default() += 1;
+    }
+    counts.into_iter().max_by_key(|&(value, count)| (count, Reverse(value))).map_or(0.0, |(value, _)| value as f32)
+}
+
+// Round key units to hundredths, so the configuration stays readable.
+fn round(units: Vec2) -> Vec2 {
+    (units * 100.0).round() / 100.0
+}
+
+impl Import {
+    // The imported keys as a configuration file, with the default settings for everything else.
+    pub fn to_toml(&self) -> String {
+        let file = ConfigFile {
+            layout: LayoutFile {
+                key_size: self.key_size,
+                key_spacing: self.key_spacing,
+            },
+            keys: self.keys.iter().map(KeyFile::from).collect(),
+        };
+        toml::to_string(&file).expect("imported keys serialize to TOML")
+    }
+}
+
+// The parts of the configuration file an import fills in.
+#[derive(Serialize)]
+struct ConfigFile {
+    layout: LayoutFile,
+    keys: Vec<KeyFile>,
+}
+
+#[derive(Serialize)]
+struct LayoutFile {
+    key_size: f32,
+    key_spacing: f32,
+}
+
+// Placement is written as `f64` so rounded values like 0.1 print as written.
+#[derive(Serialize)]
+struct KeyFile {
+    code: Binding,
+    color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "is_zero")]
+    row: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<f64>,
+}
+
+fn is_zero(row: &usize) -> bool {
+    *row == 0
+}
+
+impl From<&KeyConfig> for KeyFile {
+    fn from(key: &KeyConfig) -> Self {
+        let units = |value: Option<f32>| value.map(|value| (value as f64 * 100.0).round() / 100.0);
+        Self {
+            code: key.code,
+            color: Srgba::from(key.color).to_hex(),
+            label: key.label.clone(),
+            row: key.row,
+            x: units(key.x),
+            y: units(key.y),
+            width: units(key.width),
+            height: units(key.height),
+        }
+    }
+}
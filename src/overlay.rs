@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowLevel};
+
+use crate::binding::Binding;
+use crate::config::{Config, WindowConfig};
+
+// Turn the window into an overlay: transparent, borderless and above every other window.
+// With click-through, the cursor passes through the window to whatever lies below it.
+pub fn apply_overlay(window: &mut Window, config: &WindowConfig) {
+    if !config.overlay {
+        return;
+    }
+
+    window.transparent = true;
+    window.decorations = false;
+    window.window_level = WindowLevel::AlwaysOnTop;
+    window.cursor.hit_test = !config.click_through;
+}
+
+// Condition for the systems that only make sense in overlay mode.
+pub fn overlay_enabled(config: Res<Config>) -> bool {
+    config.window.overlay
+}
+
+// System to bring back the window's decorations while the overlay is being moved.
+// The move hotkey toggles between the borderless overlay and a decorated window that can be dragged by its title bar.
+pub fn toggle_overlay_move(
+    input: Res<ButtonInput<Binding>>,
+    config: Res<Config>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !input.just_pressed(config.hotkeys.move_overlay) {
+        return;
+    }
+
+    for mut window in windows.iter_mut() {
+        let moving = !window.decorations;
+        window.decorations = moving;
+        window.cursor.hit_test = moving || !config.window.click_through;
+        info!("{}", if moving { "Overlay can be moved" } else { "Overlay locked in place" });
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::binding::Binding;
+
+// File name of the click counts, inside the application's data directory.
+const COUNTS_FILE: &str = "counts.toml";
+
+// How often the click counts are saved while the overlay runs, in seconds.
+const SAVE_INTERVAL: f32 = 60.0;
+
+// Resource holding the lifetime and current session click counts of every binding ever pressed.
+#[derive(Resource)]
+pub struct ClickStore {
+    path: Option<PathBuf>,
+    totals: HashMap<Binding, usize>,
+    session: HashMap<Binding, usize>,
+    dirty: bool,
+    save_timer: Timer,
+}
+
+// On-disk representation of the click counts.
+#[derive(Serialize, Deserialize, Default)]
+struct SavedCounts {
+    #[serde(default)]
+    keys: Vec<SavedKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedKey {
+    code: Binding,
+    total: usize,
+    // Presses during the last session, kept separately from the lifetime total.
+    #[serde(default)]
+    session: usize,
+}
+
+impl ClickStore {
+    // Load the lifetime click counts from the data directory.
+    pub fn load() -> Self {
+        let Some(path) = dirs::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(COUNTS_FILE)) else {
+            warn!("No data directory found, click counts will not be saved");
+            return Self::new(None, HashMap::new());
+        };
+        Self::at(path)
+    }
+
+    // Load the lifetime click counts from `path`, which they are saved back to.
+    // An unreadable file is reported and left untouched, and nothing is saved during this session.
+    pub fn at(path: PathBuf) -> Self {
+        match read_counts(&path) {
+            Ok(totals) => Self::new(Some(path), totals),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::new(Some(path), HashMap::new()),
+            Err(err) => {
+                error!("Could not load click counts from {}: {err}; they will not be saved", path.display());
+                Self::new(None, HashMap::new())
+            }
+        }
+    }
+
+    // A store that starts empty and never touches the disk, used while watching replays.
+    pub fn in_memory() -> Self {
+        Self::new(None, HashMap::new())
+    }
+
+    fn new(path: Option<PathBuf>, totals: HashMap<Binding, usize>) -> Self {
+        Self {
+            path,
+            totals,
+            session: HashMap::new(),
+            dirty: false,
+            save_timer: Timer::from_seconds(SAVE_INTERVAL, TimerMode::Repeating),
+        }
+    }
+
+    // Lifetime click counts, including the current session.
+    pub fn totals(&self) -> &HashMap<Binding, usize> {
+        &self.totals
+    }
+
+    // Count a key press and return the key's new lifetime total.
+    pub fn record(&mut self, binding: Binding) -> usize {
+        *self.session.entry(binding).or_default() += 1;
+        self.dirty = true;
+
+        let total = self.totals.entry(binding).or_default();
+        *total += 1;
+        *total
+    }
+
+    // Replace all counts, e.g. after seeking in a replay.
+    pub fn reset(&mut self, totals: HashMap<Binding, usize>) {
+        self.session = totals.clone();
+        self.totals = totals;
+        self.dirty = true;
+    }
+
+    // Write the counts to disk if they changed since the last save.
+    pub fn save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty {
+            return;
+        }
+
+        let mut keys: Vec<SavedKey> = self
+            .totals
+            .iter()
+            .map(|(&code, &total)| SavedKey {
+                code,
+                total,
+                session: self.session.get(&code).copied().unwrap_or(0),
+            })
+            .collect();
+        keys.sort_by_key(|key| std::cmp::Reverse(key.total));
+
+        match write_counts(path, &SavedCounts { keys }) {
+            Ok(()) => self.dirty = false,
+            Err(err) => error!("Could not save click counts to {}: {err}", path.display()),
+        }
+    }
+}
+
+fn read_counts(path: &Path) -> io::Result<HashMap<Binding, usize>> {
+    let source = fs::read_to_string(path)?;
+    let saved: SavedCounts = toml::from_str(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(saved.keys.into_iter().map(|key| (key.code, key.total)).collect())
+}
+
+// Write the counts next to the destination first and rename them over it,
+// so a crash mid-write never leaves a truncated file behind.
+fn write_counts(path: &Path, counts: &SavedCounts) -> io::Result<()> {
+    let source = toml::to_string(counts).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temp_path = path.with_extension("toml.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(source.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+// System to save the click counts at a fixed interval.
+pub fn autosave_counts(time: Res<Time>, mut store: ResMut<ClickStore>) {
+    if store.save_timer.tick(time.delta()).just_finished() {
+        store.save();
+    }
+}
+
+// System to save the click counts when the application exits.
+pub fn save_counts_on_exit(mut exit_events: EventReader<AppExit>, mut store: ResMut<ClickStore>) {
+    if exit_events.read().next().is_some() {
+        store.save();
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::WindowResized;
+
+use crate::binding::Binding;
+use crate::config::{Config, ConfigReloaded, KeyConfig, TrailDirection};
+use crate::input::{poll_input_sources, InputEvent, InputSources, WindowInput};
+use crate::keys::{
+    rebuild_keys, relayout_keys, setup_graphics, spawn_camera, update_keys, update_trails, KeyPressed, KeyReleased, TrailMesh,
+};
+use crate::persistence::{autosave_counts, save_counts_on_exit, ClickStore};
+use crate::sound::{apply_master_volume, control_volume, drop_failed_voices, fall_back_on_failed_sounds, play_hitsounds, reload_sounds, switch_sound_pack, Mixer, Sounds};
+use crate::stats::{update_stats, PressHistory};
+
+// Plugin showing the configured keys, their trails, click counts and hitsounds in the primary window.
+//
+// Start from a configuration, or from the default one and change it with the builder methods:
+// `ClickyPlugin::new().keys([(binding, color)]).trail_speed(400.0)`.
+// Input comes from the window unless `InputSources` was inserted before the plugin was added, and the
+// click counts are kept in memory unless a `ClickStore` was. Other systems can react to the keys of the
+// layout through the `KeyPressed` and `KeyReleased` events. Sending `ConfigReloaded` after replacing
+// the `Config` resource rebuilds the keys.
+//
+// The builder methods take any value, and adding a plugin whose configuration doesn't validate panics.
+// Call `validate` first to report invalid values, e.g. ones coming from the user, as an error instead.
+#[derive(Clone)]
+pub struct ClickyPlugin {
+    config: Config,
+    camera: bool,
+    sounds: bool,
+}
+
+impl Default for ClickyPlugin {
+    fn default() -> Self {
+        Self::from_config(Config::default())
+    }
+}
+
+impl ClickyPlugin {
+    // A plugin showing the default layout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            camera: true,
+            sounds: true,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // Check the configuration the plugin was built with, which adding it would otherwise panic on.
+    pub fn validate(&self) -> Result<(), String> {
+        self.config.validate()
+    }
+
+    // Show these keys, in order, with their default labels and sounds.
+    pub fn keys(mut self, keys: impl IntoIterator<Item = (Binding, Color)>) -> Self {
+        self.config.keys = keys.into_iter().map(|(code, color)| KeyConfig::new(code, color)).collect();
+        self
+    }
+
+    // Size of the keys and the gap between them in pixels, in a window of the initial size.
+    pub fn key_size(mut self, size: f32, spacing: f32) -> Self {
+        self.config.layout.key_size = size;
+        self.config.layout.key_spacing = spacing;
+        self
+    }
+
+    pub fn direction(mut self, direction: TrailDirection) -> Self {
+        self.config.layout.direction = direction;
+        self
+    }
+
+    // Trail speed in pixels per second.
+    pub fn trail_speed(mut self, speed: f32) -> Self {
+        self.config.layout.trail_speed = speed;
+        self
+    }
+
+    // Volume of the hitsounds, and of the whole overlay's audio.
+    pub fn volume(mut self, volume: f32, master_volume: f32) -> Self {
+        self.config.sound.volume = volume;
+        self.config.sound.master_volume = master_volume;
+        self
+    }
+
+    pub fn sound_pack(mut self, pack: impl Into<String>) -> Self {
+        self.config.sound.pack = Some(pack.into());
+        self
+    }
+
+    // Whether to play hitsounds, which needs Bevy's audio plugin.
+    pub fn sounds(mut self, enabled: bool) -> Self {
+        self.sounds = enabled;
+        self
+    }
+
+    // Whether to spawn a 2D camera, for apps that bring their own.
+    pub fn camera(mut self, enabled: bool) -> Self {
+        self.camera = enabled;
+        self
+    }
+}
+
+impl Plugin for ClickyPlugin {
+    fn build(&self, app: &mut App) {
+        if let Err(err) = self.validate() {
+            panic!("invalid clicky configuration: {err}");
+        }
+
+        if !app.world().contains_resource::<InputSources>() {
+            let mut sources = InputSources::default();
+            sources.push(WindowInput::default());
+            app.insert_resource(sources);
+        }
+        if !app.world().contains_resource::<ClickStore>() {
+            app.insert_resource(ClickStore::in_memory());
+        }
+
+        app.insert_resource(self.config.clone())
+            .init_resource::<ButtonInput<Binding>>()
+            .init_resource::<PressHistory>()
+            .init_resource::<TrailMesh>()
+            .add_event::<ConfigReloaded>()
+            .add_event::<InputEvent>()
+            .add_event::<KeyPressed>()
+            .add_event::<KeyReleased>()
+            .add_systems(Startup, setup_graphics) // Set up the initial graphics and scene
+            .add_systems(PreUpdate, poll_input_sources.after(InputSystem)) // Gather presses and releases from the input sources
+            .add_systems(Update, (update_keys, update_trails)) // Update the keys and trails each frame
+            .add_systems(Update, update_stats.after(update_keys)) // Refresh the KPS and BPM readouts
+            .add_systems(Update, autosave_counts.after(update_keys)) // Save the click counts periodically
+            .add_systems(Last, save_counts_on_exit) // Save the click counts when closing
+            .add_systems(Update, rebuild_keys.run_if(on_event::<ConfigReloaded>())) // Rebuild the keys when the configuration changes
+            .add_systems(Update, relayout_keys.after(rebuild_keys).run_if(on_event::<WindowResized>().or_else(on_event::<ConfigReloaded>()))); // Fit the keys to the window
+
+        if self.camera {
+            app.add_systems(Startup, spawn_camera);
+        }
+        if self.sounds {
+            app.init_resource::<Sounds>()
+                .init_resource::<Mixer>()
+                .add_systems(Update, play_hitsounds.after(update_keys)) // Play the sounds of each press and release
+                .add_systems(Update, drop_failed_voices.before(play_hitsounds)) // Free the voices of sounds that can't play
+                .add_systems(Update, fall_back_on_failed_sounds.after(reload_sounds).after(switch_sound_pack).before(play_hitsounds)) // Replace sounds that failed to load
+                .add_systems(Update, (reload_sounds.run_if(on_event::<ConfigReloaded>()), switch_sound_pack).chain().before(play_hitsounds)) // Pick the sounds of the keys and the active sound pack
+                .add_systems(Update, (control_volume.after(reload_sounds), apply_master_volume.run_if(resource_changed::<Mixer>)).chain().before(play_hitsounds)); // Mute and change the master volume
+        }
+    }
+}
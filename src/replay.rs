@@ -0,0 +1,509 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::event::ManualEventReader;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::binding::Binding;
+use crate::config::Config;
+use crate::input::{InputEvent, InputSource};
+use crate::persistence::ClickStore;
+use crate::stats::PressHistory;
+use crate::keys::{ClicksCount, KeyID, Trail};
+
+// Magic bytes at the start of a binary replay file.
+const MAGIC: &[u8; 4] = b"CLKR";
+
+// How far the seek hotkeys jump, in seconds.
+const SEEK_STEP: f32 = 5.0;
+
+// Slowest and fastest playback speeds reachable with the speed hotkeys.
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+
+// Version of the replay format, bumped whenever its layout changes.
+pub const REPLAY_VERSION: u16 = 1;
+
+// A recorded input session: every press and release of the configured bindings.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Replay {
+    pub version: u16,
+    pub events: Vec<ReplayEvent>,
+}
+
+// A single press or release, timed from the first press of the session.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ReplayEvent {
+    #[serde(rename = "time_us", with = "micros")]
+    pub time: Duration,
+    pub binding: Binding,
+    pub state: ButtonState,
+}
+
+// File formats a replay can be stored in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayFormat {
+    // Compact binary: a header, a table of binding names and fixed size events.
+    Binary,
+    // Human readable JSON, for inspecting or editing recordings.
+    Json,
+}
+
+impl ReplayFormat {
+    // Choose the format from the file extension, defaulting to binary.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ReplayFormat::Json,
+            _ => ReplayFormat::Binary,
+        }
+    }
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            events: Vec::new(),
+        }
+    }
+
+    // Write the replay to `path`, in the format matching its extension.
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let file = File::create(path).map_err(|err| ReplayError::Io(path.to_path_buf(), err))?;
+        let mut writer = BufWriter::new(file);
+
+        let result = match ReplayFormat::from_path(path) {
+            ReplayFormat::Json => serde_json::to_writer(&mut writer, self).map_err(io::Error::from),
+            ReplayFormat::Binary => self.write_binary(&mut writer),
+        };
+        result.and_then(|()| writer.flush()).map_err(|err| ReplayError::Io(path.to_path_buf(), err))
+    }
+
+    // Read a replay from `path`, detecting binary files by their magic bytes and reading anything else as JSON.
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let bytes = fs::read(path).map_err(|err| ReplayError::Io(path.to_path_buf(), err))?;
+
+        let mut replay = match bytes.strip_prefix(MAGIC) {
+            Some(mut body) => Self::read_binary(&mut body).map_err(|err| err.to_string()),
+            None => serde_json::from_slice::<Replay>(&bytes).map_err(|err| err.to_string()),
+        }
+        .map_err(|message| ReplayError::Parse(path.to_path_buf(), message))?;
+
+        if replay.version > REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(path.to_path_buf(), replay.version));
+        }
+
+        replay.events.sort_by_key(|event| event.time);
+        Ok(replay)
+    }
+
+    // Length of the recording.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map(|event| event.time).unwrap_or_default()
+    }
+
+    fn read_binary(reader: &mut impl Read) -> io::Result<Self> {
+        let version = read_u16(reader)?;
+        if version > REPLAY_VERSION {
+            // Newer layouts cannot be parsed, report the version alone.
+            return Ok(Self {
+                version,
+                events: Vec::new(),
+            });
+        }
+
+        let mut bindings = Vec::new();
+        for _ in 0..read_u16(reader)? {
+            let mut name = vec![0; read_u8(reader)? as usize];
+            reader.read_exact(&mut name)?;
+            let binding = String::from_utf8(name)
+                .map_err(|err| err.to_string())
+                .and_then(|name| name.parse::<Binding>())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            bindings.push(binding);
+        }
+
+        let mut events = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let time = Duration::from_micros(read_u64(reader)?);
+            let index = read_u16(reader)? as usize;
+            let state = match read_u8(reader)? {
+                0 => ButtonState::Released,
+                _ => ButtonState::Pressed,
+            };
+            let binding = *bindings
+                .get(index)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("invalid binding index {index}")))?;
+            events.push(ReplayEvent { time, binding, state });
+        }
+
+        Ok(Self { version, events })
+    }
+
+    // Binary layout, all integers little endian:
+    //   magic "CLKR", version u16,
+    //   binding count u16, then per binding: name length u8, UTF-8 name,
+    //   event count u32, then per event: time in microseconds u64, binding index u16, state u8 (1 = pressed).
+    fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut bindings: Vec<Binding> = Vec::new();
+        for event in &self.events {
+            if !bindings.contains(&event.binding) {
+                bindings.push(event.binding);
+            }
+        }
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&REPLAY_VERSION.to_le_bytes())?;
+
+        writer.write_all(&(bindings.len() as u16).to_le_bytes())?;
+        for binding in &bindings {
+            let name = binding.to_string();
+            writer.write_all(&[name.len() as u8])?;
+            writer.write_all(name.as_bytes())?;
+        }
+
+        writer.write_all(&(self.events.len() as u32).to_le_bytes())?;
+        for event in &self.events {
+            let index = bindings.iter().position(|binding| *binding == event.binding).unwrap_or_default();
+            writer.write_all(&(event.time.as_micros() as u64).to_le_bytes())?;
+            writer.write_all(&(index as u16).to_le_bytes())?;
+            writer.write_all(&[u8::from(event.state == ButtonState::Pressed)])?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// Errors that can occur while reading or writing replay files.
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    UnsupportedVersion(PathBuf, u16),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(path, err) => write!(f, "could not access replay {}: {err}", path.display()),
+            ReplayError::Parse(path, message) => write!(f, "could not parse replay {}: {message}", path.display()),
+            ReplayError::UnsupportedVersion(path, version) => write!(
+                f,
+                "replay {} uses format version {version}, but only versions up to {REPLAY_VERSION} are supported",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+// Resource recording the session to a replay file.
+// Recording starts at the first press and stops at exit or when the stop hotkey is pressed.
+#[derive(Resource)]
+pub struct Recorder {
+    path: PathBuf,
+    replay: Replay,
+    start: Option<Duration>,
+    stopped: bool,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            replay: Replay::new(),
+            start: None,
+            stopped: false,
+        }
+    }
+
+    // Stop recording and write the replay file, if anything was recorded.
+    fn finish(&mut self) {
+        if self.stopped {
+            return;
+        }
+        self.stopped = true;
+
+        if self.replay.events.is_empty() {
+            info!("Nothing was recorded, not writing {}", self.path.display());
+            return;
+        }
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            if let Err(err) = fs::create_dir_all(dir) {
+                error!("Could not create {}: {err}", dir.display());
+                return;
+            }
+        }
+
+        match self.replay.save(&self.path) {
+            Ok(()) => info!("Recorded {} events to {}", self.replay.events.len(), self.path.display()),
+            Err(err) => error!("{err}"),
+        }
+    }
+}
+
+// System to record presses and releases of the configured bindings.
+pub fn record_input(
+    mut input_events: EventReader<InputEvent>,
+    input: Res<ButtonInput<Binding>>,
+    config: Res<Config>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if recorder.stopped {
+        return;
+    }
+
+    if input.just_pressed(config.hotkeys.stop_recording) {
+        recorder.finish();
+        return;
+    }
+
+    for event in input_events.read() {
+        if !config.keys.iter().any(|key| key.code == event.binding) {
+            continue;
+        }
+
+        // The recording starts with the first press, releases of keys held before that are skipped.
+        let start = match recorder.start {
+            Some(start) => start,
+            None if event.state == ButtonState::Pressed => *recorder.start.insert(event.time),
+            None => continue,
+        };
+
+        recorder.replay.events.push(ReplayEvent {
+            time: event.time.saturating_sub(start),
+            binding: event.binding,
+            state: event.state,
+        });
+    }
+}
+
+// System to write the recording when the application exits.
+pub fn finish_recording_on_exit(mut exit_events: EventReader<AppExit>, mut recorder: ResMut<Recorder>) {
+    if exit_events.read().next().is_some() {
+        recorder.finish();
+    }
+}
+
+// Marker resource present while the overlay plays back a replay instead of showing live input.
+#[derive(Resource)]
+pub struct ReplayMode;
+
+// Event asking the replay to jump forward (positive) or backward (negative) by a number of seconds.
+#[derive(Event)]
+pub struct SeekReplay(pub f32);
+
+// Event sent after the playback position jumped, so the overlay can be reset to match it.
+#[derive(Event)]
+pub struct ReplaySeeked {
+    // Presses of each binding before the new position.
+    pub counts: HashMap<Binding, usize>,
+}
+
+// Input source playing back a recorded replay.
+// The playback position follows virtual time, so pausing or changing the speed
+// of `Time<Virtual>` also pauses or speeds up the trails.
+pub struct ReplaySource {
+    replay: Replay,
+    position: Duration,
+    next_event: usize,
+    finished: bool,
+    seek_reader: ManualEventReader<SeekReplay>,
+}
+
+impl ReplaySource {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            position: Duration::ZERO,
+            next_event: 0,
+            finished: false,
+            seek_reader: ManualEventReader::default(),
+        }
+    }
+
+    // Jump to `target`, updating `bindings` to the keys held at that point without registering new presses.
+    fn seek(&mut self, target: Duration, bindings: &mut ButtonInput<Binding>) -> ReplaySeeked {
+        let target = target.min(self.replay.duration());
+        let mut held = HashSet::new();
+        let mut counts = HashMap::new();
+
+        self.next_event = 0;
+        for event in self.replay.events.iter().take_while(|event| event.time <= target) {
+            match event.state {
+                ButtonState::Pressed => {
+                    held.insert(event.binding);
+                    *counts.entry(event.binding).or_default() += 1;
+                }
+                ButtonState::Released => {
+                    held.remove(&event.binding);
+                }
+            }
+            self.next_event += 1;
+        }
+
+        bindings.reset_all();
+        for binding in held {
+            bindings.press(binding);
+            bindings.clear_just_pressed(binding);
+        }
+
+        self.position = target;
+        self.finished = false;
+        ReplaySeeked { counts }
+    }
+}
+
+impl InputSource for ReplaySource {
+    fn poll(&mut self, world: &mut World, events: &mut Vec<InputEvent>) {
+        let offset: f32 = self.seek_reader.read(world.resource::<Events<SeekReplay>>()).map(|seek| seek.0).sum();
+        if offset != 0.0 {
+            let step = Duration::from_secs_f32(offset.abs());
+            let target = if offset > 0.0 {
+                self.position + step
+            } else {
+                self.position.saturating_sub(step)
+            };
+            let seeked = self.seek(target, &mut world.resource_mut::<ButtonInput<Binding>>());
+            world.send_event(seeked);
+        }
+
+        let time = world.resource::<Time>();
+        let now = time.elapsed();
+        self.position += time.delta();
+
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.time > self.position {
+                break;
+            }
+
+            // Place the event on the virtual clock as far back as it lies behind the playback position.
+            events.push(InputEvent {
+                binding: event.binding,
+                state: event.state,
+                time: now.saturating_sub(self.position - event.time),
+            });
+            self.next_event += 1;
+        }
+
+        if !self.finished && self.next_event == self.replay.events.len() {
+            self.finished = true;
+            info!("Replay finished");
+        }
+    }
+}
+
+// System to pause, change the speed of and seek through the replay with the configured hotkeys.
+pub fn control_playback(
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<Config>,
+    mut time: ResMut<Time<Virtual>>,
+    mut seeks: EventWriter<SeekReplay>,
+) {
+    let hotkeys = &config.hotkeys;
+
+    if keys.just_pressed(hotkeys.replay_pause) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+
+    if keys.just_pressed(hotkeys.replay_faster) {
+        let speed = (time.relative_speed() * 2.0).min(MAX_SPEED);
+        time.set_relative_speed(speed);
+        info!("Playback speed {speed}x");
+    }
+    if keys.just_pressed(hotkeys.replay_slower) {
+        let speed = (time.relative_speed() / 2.0).max(MIN_SPEED);
+        time.set_relative_speed(speed);
+        info!("Playback speed {speed}x");
+    }
+
+    if keys.just_pressed(hotkeys.replay_forward) {
+        seeks.send(SeekReplay(SEEK_STEP));
+    }
+    if keys.just_pressed(hotkeys.replay_backward) {
+        seeks.send(SeekReplay(-SEEK_STEP));
+    }
+}
+
+// System to reset the counters and trails after seeking, so they match the new playback position.
+pub fn reset_after_seek(
+    mut commands: Commands,
+    mut seeked: EventReader<ReplaySeeked>,
+    mut store: ResMut<ClickStore>,
+    mut history: ResMut<PressHistory>,
+    trail_query: Query<Entity, With<Trail>>,
+    key_query: Query<(&KeyID, &Children)>,
+    mut text_query: Query<(&mut Text, &mut ClicksCount)>,
+) {
+    let Some(seek) = seeked.read().last() else {
+        return;
+    };
+
+    for entity in trail_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *history = PressHistory::default();
+    store.reset(seek.counts.clone());
+
+    for (key_id, children) in key_query.iter() {
+        let clicks = seek.counts.get(&key_id.0).copied().unwrap_or(0);
+        for &child in children.iter() {
+            if let Ok((mut text, mut clicks_count)) = text_query.get_mut(child) {
+                clicks_count.0 = clicks;
+                text.sections[0].value = clicks.to_string();
+            }
+        }
+    }
+}
+
+// Store durations as whole microseconds.
+mod micros {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(time.as_micros() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_micros)
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+
+use crate::binding::Binding;
+
+// Linux input event codes (see linux/input-event-codes.h) and the keys they stand for.
+// Codes name physical positions on a US layout, like `KeyCode` does.
+const KEYS: [(u16, KeyCode); 119] = [
+    (1, KeyCode::Escape),
+    (2, KeyCode::Digit1),
+    (3, KeyCode::Digit2),
+    (4, KeyCode::Digit3),
+    (5, KeyCode::Digit4),
+    (6, KeyCode::Digit5),
+    (7, KeyCode::Digit6),
+    (8, KeyCode::Digit7),
+    (9, KeyCode::Digit8),
+    (10, KeyCode::Digit9),
+    (11, KeyCode::Digit0),
+    (12, KeyCode::Minus),
+    (13, KeyCode::Equal),
+    (14, KeyCode::Backspace),
+    (15, KeyCode::Tab),
+    (16, KeyCode::KeyQ),
+    (17, KeyCode::KeyW),
+    (18, KeyCode::KeyE),
+    (19, KeyCode::KeyR),
+    (20, KeyCode::KeyT),
+    (21, KeyCode::KeyY),
+    (22, KeyCode::KeyU),
+    (23, KeyCode::KeyI),
+    (24, KeyCode::KeyO),
+    (25, KeyCode::KeyP),
+    (26, KeyCode::BracketLeft),
+    (27, KeyCode::BracketRight),
+    (28, KeyCode::Enter),
+    (29, KeyCode::ControlLeft),
+    (30, KeyCode::KeyA),
+    (31, KeyCode::KeyS),
+    (32, KeyCode::KeyD),
+    (33, KeyCode::KeyF),
+    (34, KeyCode::KeyG),
+    (35, KeyCode::KeyH),
+    (36, KeyCode::KeyJ),
+    (37, KeyCode::KeyK),
+    (38, KeyCode::KeyL),
+    (39, KeyCode::Semicolon),
+    (40, KeyCode::Quote),
+    (41, KeyCode::Backquote),
+    (42, KeyCode::ShiftLeft),
+    (43, KeyCode::Backslash),
+    (44, KeyCode::KeyZ),
+    (45, KeyCode::KeyX),
+    (46, KeyCode::KeyC),
+    (47, KeyCode::KeyV),
+    (48, KeyCode::KeyB),
+    (49, KeyCode::KeyN),
+    (50, KeyCode::KeyM),
+    (51, KeyCode::Comma),
+    (52, KeyCode::Period),
+    (53, KeyCode::Slash),
+    (54, KeyCode::ShiftRight),
+    (55, KeyCode::NumpadMultiply),
+    (56, KeyCode::AltLeft),
+    (57, KeyCode::Space),
+    (58, KeyCode::CapsLock),
+    (59, KeyCode::F1),
+    (60, KeyCode::F2),
+    (61, KeyCode::F3),
+    (62, KeyCode::F4),
+    (63, KeyCode::F5),
+    (64, KeyCode::F6),
+    (65, KeyCode::F7),
+    (66, KeyCode::F8),
+    (67, KeyCode::F9),
+    (68, KeyCode::F10),
+    (69, KeyCode::NumLock),
+    (70, KeyCode::ScrollLock),
+    (71, KeyCode::Numpad7),
+    (72, KeyCode::Numpad8),
+    (73, KeyCode::Numpad9),
+    (74, KeyCode::NumpadSubtract),
+    (75, KeyCode::Numpad4),
+    (76, KeyCode::Numpad5),
+    (77, KeyCode::Numpad6),
+    (78, KeyCode::NumpadAdd),
+    (79, KeyCode::Numpad1),
+    (80, KeyCode::Numpad2),
+    (81, KeyCode::Numpad3),
+    (82, KeyCode::Numpad0),
+    (83, KeyCode::NumpadDecimal),
+    (86, KeyCode::IntlBackslash),
+    (87, KeyCode::F11),
+    (88, KeyCode::F12),
+    (89, KeyCode::IntlRo),
+    (92, KeyCode::Convert),
+    (93, KeyCode::KanaMode),
+    (94, KeyCode::NonConvert),
+    (96, KeyCode::NumpadEnter),
+    (97, KeyCode::ControlRight),
+    (98, KeyCode::NumpadDivide),
+    (99, KeyCode::PrintScreen),
+    (100, KeyCode::AltRight),
+    (102, KeyCode::Home),
+    (103, KeyCode::ArrowUp),
+    (104, KeyCode::PageUp),
+    (105, KeyCode::ArrowLeft),
+    (106, KeyCode::ArrowRight),
+    (107, KeyCode::End),
+    (108, KeyCode::ArrowDown),
+    (109, KeyCode::PageDown),
+    (110, KeyCode::Insert),
+    (111, KeyCode::Delete),
+    (113, KeyCode::AudioVolumeMute),
+    (114, KeyCode::AudioVolumeDown),
+    (115, KeyCode::AudioVolumeUp),
+    (117, KeyCode::NumpadEqual),
+    (119, KeyCode::Pause),
+    (121, KeyCode::NumpadComma),
+    (122, KeyCode::Lang1),
+    (123, KeyCode::Lang2),
+    (124, KeyCode::IntlYen),
+    (125, KeyCode::SuperLeft),
+    (126, KeyCode::SuperRight),
+    (127, KeyCode::ContextMenu),
+    (163, KeyCode::MediaTrackNext),
+    (164, KeyCode::MediaPlayPause),
+];
+
+// Linux event codes of the mouse buttons (BTN_LEFT to BTN_BACK).
+// Mice report their side buttons either as BTN_SIDE and BTN_EXTRA or as BTN_BACK and BTN_FORWARD.
+const MOUSE_BUTTONS: [(u16, MouseButton); 7] = [
+    (0x110, MouseButton::Left),
+    (0x111, MouseButton::Right),
+    (0x112, MouseButton::Middle),
+    (0x113, MouseButton::Back),
+    (0x114, MouseButton::Forward),
+    (0x115, MouseButton::Forward),
+    (0x116, MouseButton::Back),
+];
+
+// Codes of F13 to F24, which are contiguous on Linux.
+const F13_CODE: u16 = 183;
+const F13_TO_F24: [KeyCode; 12] = [
+    KeyCode::F13,
+    KeyCode::F14,
+    KeyCode::F15,
+    KeyCode::F16,
+    KeyCode::F17,
+    KeyCode::F18,
+    KeyCode::F19,
+    KeyCode::F20,
+    KeyCode::F21,
+    KeyCode::F22,
+    KeyCode::F23,
+    KeyCode::F24,
+];
+
+// Binding of a Linux key or button event code, if the overlay knows it.
+pub fn binding_from_linux_code(code: u16) -> Option<Binding> {
+    if let Some(&(_, key_code)) = KEYS.iter().find(|(other, _)| *other == code) {
+        return Some(Binding::Key(key_code));
+    }
+    if let Some(&(_, button)) = MOUSE_BUTTONS.iter().find(|(other, _)| *other == code) {
+        return Some(Binding::Mouse(button));
+    }
+    code.checked_sub(F13_CODE)
+        .and_then(|index| F13_TO_F24.get(index as usize))
+        .map(|&key_code| Binding::Key(key_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_mouse_buttons_have_bindings() {
+        assert_eq!(binding_from_linux_code(16), Some(Binding::Key(KeyCode::KeyQ)));
+        assert_eq!(binding_from_linux_code(0x110), Some(Binding::Mouse(MouseButton::Left)));
+        // BTN_SIDE and BTN_EXTRA are the back and forward buttons
+        assert_eq!(binding_from_linux_code(0x113), Some(Binding::Mouse(MouseButton::Back)));
+        assert_eq!(binding_from_linux_code(0x114), Some(Binding::Mouse(MouseButton::Forward)));
+        // So are BTN_FORWARD and BTN_BACK
+        assert_eq!(binding_from_linux_code(0x115), Some(Binding::Mouse(MouseButton::Forward)));
+        assert_eq!(binding_from_linux_code(0x116), Some(Binding::Mouse(MouseButton::Back)));
+        assert_eq!(binding_from_linux_code(0), None);
+        assert_eq!(binding_from_linux_code(0x117), None);
+    }
+
+    #[test]
+    fn f13_to_f24_are_contiguous() {
+        assert_eq!(binding_from_linux_code(182), None);
+        assert_eq!(binding_from_linux_code(183), Some(Binding::Key(KeyCode::F13)));
+        assert_eq!(binding_from_linux_code(194), Some(Binding::Key(KeyCode::F24)));
+        assert_eq!(binding_from_linux_code(195), None);
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::{AssetLoadFailedEvent, LoadState};
+use bevy::audio::{GlobalVolume, Volume};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::binding::Binding;
+use crate::config::Config;
+use crate::keys::{KeyPressed, KeyReleased};
+
+// Path of the sound played when nothing else is configured, relative to the assets directory.
+const DEFAULT_SOUND_PATH: &str = "audio/hitsound.wav";
+
+// Directory of the sound packs inside the assets directory, holding one directory per pack.
+const PACKS_DIR: &str = "sounds";
+
+// File describing a sound pack, at the root of its directory.
+const MANIFEST_FILE: &str = "pack.toml";
+
+// How much the volume hotkeys change the master volume.
+const VOLUME_STEP: f32 = 0.1;
+
+// A sound pack's manifest. Sound paths are relative to the pack's directory.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct PackManifest {
+    // Sounds of every key the pack doesn't list under `keys`.
+    press: Option<String>,
+    release: Option<String>,
+    volume: Option<f32>,
+    #[serde(default)]
+    keys: HashMap<Binding, PackKey>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct PackKey {
+    press: Option<String>,
+    release: Option<String>,
+}
+
+// A sound ready to be played for a key.
+#[derive(Clone)]
+struct KeySound {
+    source: Handle<AudioSource>,
+    volume: f32,
+}
+
+// Resource holding the press and release sounds of every key in the layout, and the available sound packs.
+#[derive(Resource)]
+pub struct Sounds {
+    default: Handle<AudioSource>,
+    // Names of the packs found in the assets directory, and the one in use.
+    packs: Vec<String>,
+    active_pack: Option<usize>,
+    press: HashMap<Binding, KeySound>,
+    release: HashMap<Binding, KeySound>,
+}
+
+impl FromWorld for Sounds {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let config = world.resource::<Config>();
+        let mut sounds = Self {
+            default: asset_server.load(DEFAULT_SOUND_PATH),
+            packs: Vec::new(),
+            active_pack: None,
+            press: HashMap::new(),
+            release: HashMap::new(),
+        };
+        sounds.reset(config, asset_server);
+        sounds
+    }
+}
+
+impl Sounds {
+    // Look for sound packs again, switch to the configured one and load the sounds of the layout.
+    fn reset(&mut self, config: &Config, asset_server: &AssetServer) {
+        self.packs = find_packs();
+        self.active_pack = config.sound.pack.as_ref().and_then(|name| {
+            let index = self.packs.iter().position(|pack| pack == name);
+            if index.is_none() {
+                warn!("Sound pack {name:?} not found in {}, using the default sounds", assets_dir().join(PACKS_DIR).display());
+            }
+            index
+        });
+        self.load(config, asset_server);
+    }
+
+    // Switch to the next sound pack, or back to the default sounds after the last one.
+    fn next_pack(&mut self, config: &Config, asset_server: &AssetServer) {
+        self.active_pack = match self.active_pack {
+            None if !self.packs.is_empty() => Some(0),
+            Some(index) if index + 1 < self.packs.len() => Some(index + 1),
+            _ => None,
+        };
+        match self.active_pack {
+            Some(index) => info!("Using sound pack {:?}", self.packs[index]),
+            None => info!("Using the default sounds"),
+        }
+        self.load(config, asset_server);
+    }
+
+    // Pick the sounds of each key: its own, then the pack's sounds for that key, then the pack's, then the default.
+    fn load(&mut self, config: &Config, asset_server: &AssetServer) {
+        let pack = self.active_pack.map(|index| self.packs[index].as_str());
+        let manifest = pack.and_then(load_manifest).unwrap_or_default();
+        let pack_path = |file: &String| format!("{PACKS_DIR}/{}/{file}", pack.unwrap_or_default());
+
+        self.press.clear();
+        self.release.clear();
+        for key in &config.keys {
+            let pack_key = manifest.keys.get(&key.code);
+            let volume = key.volume.or(manifest.volume).unwrap_or(config.sound.volume);
+
+            let press = key.sound.clone().or_else(|| {
+                pack_key
+                    .and_then(|pack_key| pack_key.press.as_ref())
+                    .or(manifest.press.as_ref())
+                    .map(pack_path)
+            });
+            let source = match press {
+                Some(path) => self.load_or_default(path, asset_server),
+                None => self.default.clone(),
+            };
+            self.press.insert(key.code, KeySound { source, volume });
+
+            let release = key.release_sound.clone().or_else(|| {
+                pack_key
+                    .and_then(|pack_key| pack_key.release.as_ref())
+                    .or(manifest.release.as_ref())
+                    .map(pack_path)
+            });
+            if let Some(path) = release {
+                let source = self.load_or_default(path, asset_server);
+                self.release.insert(key.code, KeySound { source, volume });
+            }
+        }
+    }
+
+    // Missing files are caught here, files that fail to load later by `fall_back_on_failed_sounds`.
+    fn load_or_default(&self, path: String, asset_server: &AssetServer) -> Handle<AudioSource> {
+        if assets_dir().join(&path).is_file() {
+            asset_server.load(path)
+        } else {
+            warn!("Sound {path} not found, using the default hitsound instead");
+            self.default.clone()
+        }
+    }
+}
+
+// System to replace the sounds that failed to load, e.g. in an unsupported format or corrupt, with the default hitsound.
+pub fn fall_back_on_failed_sounds(mut failed_events: EventReader<AssetLoadFailedEvent<AudioSource>>, mut sounds: ResMut<Sounds>) {
+    for event in failed_events.read() {
+        if event.id == sounds.default.id() {
+            continue;
+        }
+        warn!("Sound {} could not be loaded ({}), using the default hitsound instead", event.path, event.error);
+        let default = sounds.default.clone();
+        let Sounds { press, release, .. } = &mut *sounds;
+        for sound in press.values_mut().chain(release.values_mut()) {
+            if sound.source.id() == event.id {
+                sound.source = default.clone();
+            }
+        }
+    }
+}
+
+// Resource holding the master volume and whether sounds are muted, which the hotkeys change at runtime.
+#[derive(Resource)]
+pub struct Mixer {
+    master_volume: f32,
+    muted: bool,
+}
+
+impl FromWorld for Mixer {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            master_volume: world.resource::<Config>().sound.master_volume,
+            muted: false,
+        }
+    }
+}
+
+// Marks the entity of a playing sound, to count how many play at once.
+#[derive(Component)]
+pub struct Voice;
+
+// Directory the asset server loads from.
+fn assets_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets")
+}
+
+// Names of the directories in the sound packs directory that hold a manifest, sorted by name.
+fn find_packs() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(assets_dir().join(PACKS_DIR)) else {
+        return Vec::new();
+    };
+    let mut packs: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    packs.sort();
+    packs
+}
+
+// Read a pack's manifest, reporting a broken one and using the default sounds instead.
+fn load_manifest(pack: &str) -> Option<PackManifest> {
+    let path = assets_dir().join(PACKS_DIR).join(pack).join(MANIFEST_FILE);
+    let manifest = fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| toml::from_str(&source).map_err(|err| err.to_string()));
+    match manifest {
+        Ok(manifest) => Some(manifest),
+        Err(err) => {
+            warn!("Could not load sound pack {}: {err}; using the default sounds", path.display());
+            None
+        }
+    }
+}
+
+// System to play the sounds of the keys in the layout as they are pressed and released.
+// Each sound gets an entity of its own that despawns once it finished playing, so it outlives the trail.
+// Sounds past the voice limit or too close to the previous one are skipped, so fast bursts stay audible.
+#[allow(clippy::too_many_arguments)]
+pub fn play_hitsounds(
+    mut commands: Commands,
+    mut pressed_events: EventReader<KeyPressed>,
+    mut released_events: EventReader<KeyReleased>,
+    config: Res<Config>,
+    sounds: Res<Sounds>,
+    mixer: Res<Mixer>,
+    voices: Query<(), With<Voice>>,
+    mut last_played: Local<Option<Duration>>,
+) {
+    if mixer.muted {
+        pressed_events.clear();
+        released_events.clear();
+        return;
+    }
+
+    let min_interval = Duration::from_millis(config.sound.min_interval_ms);
+    let mut playing = voices.iter().count();
+    let presses = pressed_events.read().map(|event| (event.binding, event.time, &sounds.press));
+    let releases = released_events.read().map(|event| (event.binding, event.time, &sounds.release));
+    for (binding, time, sounds) in presses.chain(releases) {
+        let Some(sound) = sounds.get(&binding) else {
+            continue;
+        };
+        // A seek back in a replay moves time backwards, which never counts as too close
+        let too_close = last_played.is_some_and(|last| time >= last && time - last < min_interval);
+        if playing >= config.sound.max_voices || too_close {
+            continue;
+        }
+        *last_played = Some(time);
+        playing += 1;
+
+        let volume = sound.volume * vary(config.sound.volume_variation);
+        let speed = vary(config.sound.pitch_variation); // Playing faster raises the pitch
+        commands.spawn((
+            AudioBundle {
+                source: sound.source.clone(),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)).with_speed(speed),
+            },
+            Voice,
+        ));
+    }
+}
+
+// System to despawn the sounds whose source failed to load, e.g. an unsupported format or a corrupt file.
+// Bevy only despawns finished sounds that got a sink, which these never get, so they would hold a voice forever.
+#[allow(clippy::type_complexity)]
+pub fn drop_failed_voices(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    voices: Query<(Entity, &Handle<AudioSource>), (With<Voice>, Without<AudioSink>)>,
+) {
+    for (entity, source) in voices.iter() {
+        if matches!(asset_server.load_state(source), LoadState::Failed(_)) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Random factor within `variation` of 1.0.
+fn vary(variation: f32) -> f32 {
+    1.0 + variation * (fastrand::f32() * 2.0 - 1.0)
+}
+
+// System to pick the sounds of the new layout and the master volume after the configuration file was reloaded.
+pub fn reload_sounds(
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    mut sounds: ResMut<Sounds>,
+    mut mixer: ResMut<Mixer>,
+) {
+    sounds.reset(&config, &asset_server);
+    mixer.master_volume = config.sound.master_volume;
+}
+
+// System to mute and change the master volume with the hotkeys.
+pub fn control_volume(input: Res<ButtonInput<Binding>>, config: Res<Config>, mut mixer: ResMut<Mixer>) {
+    let hotkeys = &config.hotkeys;
+    if input.just_pressed(hotkeys.mute) {
+        mixer.muted = !mixer.muted;
+        info!("{}", if mixer.muted { "Sounds muted" } else { "Sounds unmuted" });
+    }
+
+    let step = match (input.just_pressed(hotkeys.volume_up), input.just_pressed(hotkeys.volume_down)) {
+        (true, false) => VOLUME_STEP,
+        (false, true) => -VOLUME_STEP,
+        _ => return,
+    };
+    mixer.master_volume = (mixer.master_volume + step).max(0.0);
+    info!("Master volume {:.0}%", mixer.master_volume * 100.0);
+}
+
+// System to apply the master volume to the sounds played from now on.
+pub fn apply_master_volume(mixer: Res<Mixer>, mut global_volume: ResMut<GlobalVolume>) {
+    let volume = if mixer.muted { 0.0 } else { mixer.master_volume };
+    *global_volume = GlobalVolume::new(volume);
+}
+
+// System to switch to the next sound pack when its hotkey is pressed.
+pub fn switch_sound_pack(
+    input: Res<ButtonInput<Binding>>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    mut sounds: ResMut<Sounds>,
+) {
+    if input.just_pressed(config.hotkeys.next_sound_pack) {
+        sounds.next_pack(&config, &asset_server);
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::binding::Binding;
+use crate::keys::{KeyID, KeyPressed};
+
+// Length of the rolling window used to compute keys per second, in seconds.
+const KPS_WINDOW: f64 = 1.0;
+
+// Beats per minute for a given KPS, assuming 1/4 streams (four presses per beat).
+const BPM_PER_KPS: f32 = 15.0;
+
+// Resource holding the timestamps of recent key presses and the highest KPS reached.
+#[derive(Resource, Default)]
+pub struct PressHistory {
+    presses: VecDeque<(Binding, f64)>,
+    peak_kps: f32,
+}
+
+impl PressHistory {
+    // Record a key press that happened at `time` seconds.
+    pub fn push(&mut self, binding: Binding, time: f64) {
+        self.presses.push_back((binding, time));
+    }
+
+    // Forget presses that fell out of the rolling window ending at `now`.
+    fn prune(&mut self, now: f64) {
+        while let Some(&(_, time)) = self.presses.front() {
+            if now - time <= KPS_WINDOW {
+                break;
+            }
+            self.presses.pop_front();
+        }
+    }
+
+    // Keys per second over the rolling window, for all keys together.
+    pub fn kps(&self) -> f32 {
+        self.presses.len() as f32 / KPS_WINDOW as f32
+    }
+
+    // Keys per second over the rolling window, for a single key.
+    pub fn key_kps(&self, binding: Binding) -> f32 {
+        self.presses.iter().filter(|(other, _)| *other == binding).count() as f32 / KPS_WINDOW as f32
+    }
+}
+
+// Marker for the text showing the total KPS, peak KPS and BPM above the keys.
+#[derive(Component)]
+pub struct StatsText;
+
+// Marker for the text showing the KPS of a single key, spawned as a child of the key.
+#[derive(Component)]
+pub struct KeyKpsText;
+
+// Spawn the text showing the total KPS, peak KPS and BPM at the given position.
+pub fn spawn_stats_text(commands: &mut Commands, position: Vec2, anchor: Anchor) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                stats_label(0.0, 0.0),
+                TextStyle {
+                    font: Default::default(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            ).with_justify(JustifyText::Center),
+            text_anchor: anchor,
+            transform: Transform::from_translation(position.extend(2.0)), // Draw above the trails
+            ..Default::default()
+        },
+        StatsText,
+    ));
+}
+
+// System to record new presses, drop old ones, track the peak KPS and refresh the KPS and BPM readouts.
+pub fn update_stats(
+    time: Res<Time>,
+    mut pressed_events: EventReader<KeyPressed>,
+    mut history: ResMut<PressHistory>,
+    key_query: Query<(&KeyID, &Children)>,
+    mut key_text_query: Query<&mut Text, (With<KeyKpsText>, Without<StatsText>)>,
+    mut stats_text_query: Query<&mut Text, (With<StatsText>, Without<KeyKpsText>)>,
+) {
+    for event in pressed_events.read() {
+        history.push(event.binding, event.time.as_secs_f64());
+    }
+    history.prune(time.elapsed_seconds_f64());
+
+    let kps = history.kps();
+    history.peak_kps = history.peak_kps.max(kps);
+
+    for mut text in stats_text_query.iter_mut() {
+        text.sections[0].value = stats_label(kps, history.peak_kps);
+    }
+
+    for (key_id, children) in key_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = key_text_query.get_mut(child) {
+                text.sections[0].value = format!("{:.0}", history.key_kps(key_id.0));
+            }
+        }
+    }
+}
+
+fn stats_label(kps: f32, peak_kps: f32) -> String {
+    format!("{kps:.0} KPS | {:.0} BPM | peak {peak_kps:.0} KPS", kps * BPM_PER_KPS)
+}
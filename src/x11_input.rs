@@ -0,0 +1,168 @@
+use std::thread;
+use std::time::Instant;
+
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use crossbeam_channel::Sender;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xinput::{self, ConnectionExt as _, KeyEventFlags, XIEventMask};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::binding::Binding;
+use crate::input::{ChannelSource, ExternalInput};
+use crate::scancode::binding_from_linux_code;
+
+// X11 keycodes are the Linux event codes shifted by 8 on every evdev-based server.
+const KEYCODE_OFFSET: u32 = 8;
+
+// Core pointer buttons and the bindings they stand for; 4 and 5 are the scroll wheel.
+const BUTTONS: [(u32, Binding); 7] = [
+    (1, Binding::Mouse(MouseButton::Left)),
+    (2, Binding::Mouse(MouseButton::Middle)),
+    (3, Binding::Mouse(MouseButton::Right)),
+    (4, Binding::ScrollUp),
+    (5, Binding::ScrollDown),
+    (8, Binding::Mouse(MouseButton::Back)),
+    (9, Binding::Mouse(MouseButton::Forward)),
+];
+
+// Subscribe to raw key and button events on the X server named by `$DISPLAY` and return the source they feed.
+// Raw events reach us whichever window has focus and need no special permissions.
+pub fn spawn_x11_source() -> Result<ChannelSource, String> {
+    let (connection, screen) = x11rb::connect(None).map_err(|err| format!("could not connect to the X server: {err}"))?;
+    select_raw_events(&connection, screen)?;
+
+    let (source, sender) = ChannelSource::new();
+    thread::Builder::new()
+        .name("x11 input".to_string())
+        .spawn(move || {
+            if let Err(err) = read_events(&connection, &sender) {
+                error!("Stopped reading X11 input: {err}");
+            }
+        })
+        .map_err(|err| format!("could not start the X11 reader thread: {err}"))?;
+    Ok(source)
+}
+
+// Ask for the raw events of all master devices, which requires XInput 2.1.
+fn select_raw_events(connection: &RustConnection, screen: usize) -> Result<(), String> {
+    let supported = supports_raw_events_during_grabs(connection).map_err(|err| format!("could not query XInput: {err}"))?;
+    if !supported {
+        return Err("the X server does not support XInput 2.1".to_string());
+    }
+
+    let root = connection.setup().roots[screen].root;
+    let mask = XIEventMask::RAW_KEY_PRESS
+        | XIEventMask::RAW_KEY_RELEASE
+        | XIEventMask::RAW_BUTTON_PRESS
+        | XIEventMask::RAW_BUTTON_RELEASE;
+    let masks = [xinput::EventMask {
+        deviceid: xinput::Device::ALL_MASTER.into(),
+        mask: vec![mask],
+    }];
+    connection
+        .xinput_xi_select_events(root, &masks)
+        .map_err(ReplyError::from)
+        .and_then(|cookie| cookie.check())
+        .map_err(|err| format!("could not select XInput 2 raw events: {err}"))
+}
+
+// Raw events keep arriving while another client grabs the keyboard, as fullscreen games often do, only for
+// clients that announced XInput 2.1 or later, so announce 2.2 and require the server to support at least 2.1.
+fn supports_raw_events_during_grabs(connection: &RustConnection) -> Result<bool, ReplyError> {
+    if connection.extension_information(xinput::X11_EXTENSION_NAME)?.is_none() {
+        return Ok(false);
+    }
+    let version = connection.xinput_xi_query_version(2, 2)?.reply()?;
+    Ok(version.major_version > 2 || (version.major_version == 2 && version.minor_version >= 1))
+}
+
+// Wait on the X connection and pass each raw event on, timed on arrival since X timestamps use another clock.
+fn read_events(connection: &RustConnection, sender: &Sender<ExternalInput>) -> Result<(), String> {
+    loop {
+        let event = connection.wait_for_event().map_err(|err| err.to_string())?;
+        let instant = Instant::now();
+        for (binding, state) in translate(&event) {
+            if sender.send(ExternalInput { binding, state, instant }).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Presses and releases described by a raw event. The server flags auto-repeated key presses with
+// KEY_REPEAT, and reports wheel ticks as presses of buttons 4 and 5 with a matching release.
+fn translate(event: &Event) -> Vec<(Binding, ButtonState)> {
+    let key = |keycode: u32, state| {
+        keycode
+            .checked_sub(KEYCODE_OFFSET)
+            .and_then(|code| u16::try_from(code).ok())
+            .and_then(binding_from_linux_code)
+            .map(|binding| (binding, state))
+            .into_iter()
+            .collect()
+    };
+    let button = |detail: u32| BUTTONS.iter().find(|(other, _)| *other == detail).map(|&(_, binding)| binding);
+
+    match event {
+        Event::XinputRawKeyPress(event) if !event.flags.contains(KeyEventFlags::KEY_REPEAT) => {
+            key(event.detail, ButtonState::Pressed)
+        }
+        Event::XinputRawKeyRelease(event) => key(event.detail, ButtonState::Released),
+        Event::XinputRawButtonPress(event) => match button(event.detail) {
+            Some(binding @ (Binding::ScrollUp | Binding::ScrollDown)) => {
+                vec![(binding, ButtonState::Pressed), (binding, ButtonState::Released)]
+            }
+            Some(binding) => vec![(binding, ButtonState::Pressed)],
+            None => Vec::new(),
+        },
+        Event::XinputRawButtonRelease(event) => match button(event.detail) {
+            Some(Binding::ScrollUp | Binding::ScrollDown) | None => Vec::new(),
+            Some(binding) => vec![(binding, ButtonState::Released)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xinput::{RawButtonPressEvent, RawKeyPressEvent};
+
+    // X11 keycode of Q, evdev code 16 shifted by the offset.
+    const KEYCODE_Q: u32 = 24;
+
+    fn key(detail: u32, flags: KeyEventFlags) -> RawKeyPressEvent {
+        RawKeyPressEvent { detail, flags, ..Default::default() }
+    }
+
+    fn button(detail: u32) -> RawButtonPressEvent {
+        RawButtonPressEvent { detail, ..Default::default() }
+    }
+
+    #[test]
+    fn keys_are_offset_and_repeats_ignored() {
+        let q = Binding::Key(KeyCode::KeyQ);
+        let press = key(KEYCODE_Q, KeyEventFlags::default());
+        assert_eq!(translate(&Event::XinputRawKeyPress(press.clone())), [(q, ButtonState::Pressed)]);
+        assert_eq!(translate(&Event::XinputRawKeyRelease(press)), [(q, ButtonState::Released)]);
+        assert_eq!(translate(&Event::XinputRawKeyPress(key(KEYCODE_Q, KeyEventFlags::KEY_REPEAT))), []);
+        // Keycodes below the offset have no evdev code
+        assert_eq!(translate(&Event::XinputRawKeyPress(key(7, KeyEventFlags::default()))), []);
+    }
+
+    #[test]
+    fn buttons_press_and_release_and_scroll_ticks_do_both() {
+        let left = Binding::Mouse(MouseButton::Left);
+        assert_eq!(translate(&Event::XinputRawButtonPress(button(1))), [(left, ButtonState::Pressed)]);
+        assert_eq!(translate(&Event::XinputRawButtonRelease(button(1))), [(left, ButtonState::Released)]);
+
+        let tick = |binding| [(binding, ButtonState::Pressed), (binding, ButtonState::Released)];
+        assert_eq!(translate(&Event::XinputRawButtonPress(button(4))), tick(Binding::ScrollUp));
+        assert_eq!(translate(&Event::XinputRawButtonPress(button(5))), tick(Binding::ScrollDown));
+        assert_eq!(translate(&Event::XinputRawButtonRelease(button(4))), []);
+        assert_eq!(translate(&Event::XinputRawButtonPress(button(6))), []);
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use clicky_rs::config::{Config, InputBackend, KeyConfig};
+use clicky_rs::ClickyPlugin;
+
+// The default configuration with its first key changed by `change`.
+fn with_first_key(change: impl FnOnce(&mut KeyConfig)) -> Config {
+    let mut config = Config::default();
+    change(&mut config.keys[0]);
+    config
+}
+
+#[test]
+fn default_configuration_is_valid() {
+    assert_eq!(Config::default().validate(), Ok(()));
+}
+
+#[test]
+fn key_placement_must_be_finite() {
+    for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        assert!(with_first_key(|key| key.x = Some(value)).validate().is_err(), "x = {value}");
+        assert!(with_first_key(|key| key.y = Some(value)).validate().is_err(), "y = {value}");
+        assert!(with_first_key(|key| key.width = Some(value)).validate().is_err(), "width = {value}");
+        assert!(with_first_key(|key| key.height = Some(value)).validate().is_err(), "height = {value}");
+    }
+    // Negative offsets are fine, they only shift the key back
+    assert_eq!(with_first_key(|key| key.x = Some(-0.5)).validate(), Ok(()));
+}
+
+#[test]
+fn sound_volumes_must_be_finite_and_not_negative() {
+    for value in [f32::NAN, f32::INFINITY, -0.1] {
+        let mut config = Config::default();
+        config.sound.volume = value;
+        assert!(config.validate().is_err(), "volume = {value}");
+        let mut config = Config::default();
+        config.sound.master_volume = value;
+        assert!(config.validate().is_err(), "master_volume = {value}");
+    }
+}
+
+#[test]
+fn key_volumes_must_be_finite_and_not_negative() {
+    for value in [f32::NAN, f32::INFINITY, -0.1] {
+        assert!(with_first_key(|key| key.volume = Some(value)).validate().is_err(), "volume = {value}");
+    }
+    assert_eq!(with_first_key(|key| key.volume = Some(0.0)).validate(), Ok(()));
+}
+
+#[test]
+fn click_through_needs_a_global_backend() {
+    let mut config = Config::default();
+    config.window.overlay = true;
+    config.window.click_through = true;
+    assert!(config.validate().is_err());
+    for backend in [InputBackend::Evdev, InputBackend::X11] {
+        config.input.backend = backend;
+        assert_eq!(config.validate(), Ok(()), "{}", backend.name());
+    }
+}
+
+#[test]
+fn builder_values_are_validated() {
+    assert_eq!(ClickyPlugin::new().trail_speed(400.0).validate(), Ok(()));
+    assert!(ClickyPlugin::new().trail_speed(f32::NAN).validate().is_err());
+    assert!(ClickyPlugin::new().key_size(80.0, -1.0).validate().is_err());
+    assert!(ClickyPlugin::new().volume(1.0, f32::INFINITY).validate().is_err());
+    assert!(ClickyPlugin::new().keys([]).validate().is_err());
+}
+
+#[test]
+#[should_panic(expected = "invalid clicky configuration")]
+fn adding_an_invalid_plugin_panics() {
+    App::new().add_plugins(ClickyPlugin::new().trail_speed(0.0));
+}
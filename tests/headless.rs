@@ -0,0 +1,419 @@
+use std::time::Duration;
+
+use bevy::input::gamepad::{GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent, GamepadInfo};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::{ButtonState, InputPlugin};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::PrimaryWindow;
+
+use clicky_rs::binding::Binding;
+use clicky_rs::config::{Config, ConfigReloaded, KeyConfig, TrailDirection};
+use clicky_rs::input::{HotkeysOnly, InputEvent, InputSource, InputSources, ScriptedSource, WindowInput};
+use clicky_rs::keys::{update_keys, window_size, ClicksCount, KeyID, KeyReleased, Trail, TrailStart};
+use clicky_rs::replay::{reset_after_seek, Replay, ReplayEvent, ReplaySeeked, ReplaySource, SeekReplay};
+use clicky_rs::ClickyPlugin;
+
+// Every frame advances the clock by exactly this much, so trail geometry is predictable.
+const FRAME: Duration = Duration::from_millis(10);
+
+const KEY: Binding = Binding::Key(KeyCode::KeyQ);
+
+// An app running the default overlay without a renderer, audio or a real window, fed by `source`.
+fn headless_app(source: impl InputSource) -> App {
+    headless_app_with(Config::default(), source)
+}
+
+fn headless_app_with(config: Config, source: impl InputSource) -> App {
+    let mut app = unstarted_app(config, source);
+    app.update(); // Run the startup systems
+    app
+}
+
+// The window plugin only spawns the primary window entity, since nothing opens it.
+fn unstarted_app(config: Config, source: impl InputSource) -> App {
+    let plugin = ClickyPlugin::from_config(config).sounds(false);
+    let window = Window {
+        resolution: window_size(plugin.config()).into(),
+        ..Default::default()
+    };
+    let mut sources = InputSources::default();
+    sources.push(source);
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), InputPlugin))
+        .add_plugins(WindowPlugin {
+            primary_window: Some(window),
+            ..Default::default()
+        })
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .insert_resource(sources)
+        .add_plugins(plugin);
+    app
+}
+
+fn trail_speed(app: &App) -> f32 {
+    app.world().resource::<Config>().layout.trail_speed
+}
+
+fn press(binding: Binding, time: Duration) -> InputEvent {
+    InputEvent { binding, state: ButtonState::Pressed, time }
+}
+
+fn release(binding: Binding, time: Duration) -> InputEvent {
+    InputEvent { binding, state: ButtonState::Released, time }
+}
+
+fn keyboard(state: ButtonState) -> KeyboardInput {
+    KeyboardInput {
+        key_code: KeyCode::KeyQ,
+        logical_key: Key::Character("q".into()),
+        state,
+        window: Entity::PLACEHOLDER,
+    }
+}
+
+// Run frames until the clock reaches `time`.
+fn run_until(app: &mut App, time: Duration) {
+    while now(app) < time {
+        app.update();
+    }
+}
+
+fn now(app: &App) -> Duration {
+    app.world().resource::<Time>().elapsed()
+}
+
+fn clicks(app: &mut App, binding: Binding) -> usize {
+    let world = app.world_mut();
+    let children: Vec<Entity> = world
+        .query::<(&KeyID, &Children)>()
+        .iter(world)
+        .find(|(key_id, _)| key_id.0 == binding)
+        .map(|(_, children)| children.to_vec())
+        .expect("key is in the layout");
+    children.into_iter().find_map(|child| world.get::<ClicksCount>(child)).map(|count| count.0).expect("key shows its count")
+}
+
+fn trails(app: &mut App) -> Vec<(Trail, Transform)> {
+    let world = app.world_mut();
+    world.query::<(&Trail, &Transform)>().iter(world).map(|(trail, transform)| (*trail, *transform)).collect()
+}
+
+// Whether `binding` has a trail that is still growing, i.e. its key is held.
+fn held(app: &mut App, binding: Binding) -> bool {
+    trails(app).iter().any(|(trail, _)| trail.key == binding && trail.is_active())
+}
+
+// Move a gamepad button or trigger to `value` and run a frame.
+fn set_gamepad_button(app: &mut App, gamepad: usize, button: GamepadButtonType, value: f32) {
+    let event = GamepadButtonChangedEvent::new(Gamepad::new(gamepad), button, value);
+    app.world_mut().send_event(GamepadEvent::from(event));
+    app.update();
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-2, "expected {expected}, got {actual}");
+}
+
+#[test]
+fn presses_within_a_frame_all_count() {
+    let mut app = headless_app(WindowInput::default());
+    app.world_mut().send_event_batch([
+        keyboard(ButtonState::Pressed),
+        keyboard(ButtonState::Released),
+        keyboard(ButtonState::Pressed),
+        keyboard(ButtonState::Released),
+    ]);
+    app.update();
+
+    assert_eq!(clicks(&mut app, KEY), 2);
+    let trails = trails(&mut app);
+    assert_eq!(trails.len(), 2);
+    assert!(trails.iter().all(|(trail, _)| !trail.is_active()));
+}
+
+#[test]
+fn key_repeats_are_not_presses() {
+    let mut app = headless_app(WindowInput::default());
+    app.world_mut().send_event(keyboard(ButtonState::Pressed));
+    app.update();
+    app.world_mut().send_event(keyboard(ButtonState::Pressed));
+    app.update();
+
+    assert_eq!(clicks(&mut app, KEY), 1);
+    assert_eq!(trails(&mut app).len(), 1);
+}
+
+#[test]
+fn held_trail_grows_from_its_key() {
+    let mut app = headless_app(ScriptedSource::new(vec![press(KEY, Duration::from_millis(25))]));
+    run_until(&mut app, Duration::from_millis(500));
+
+    let [(trail, transform)] = trails(&mut app)[..] else {
+        panic!("expected a single trail");
+    };
+    assert!(trail.is_active());
+    let length = (now(&app) - Duration::from_millis(25)).as_secs_f32() * trail_speed(&app);
+    assert_close(transform.scale.y, length);
+    // Trails move up by default, and the near end stays on the key's edge.
+    assert_close(transform.translation.y - transform.scale.y / 2.0, trail.origin.y);
+}
+
+#[test]
+fn trail_length_matches_hold_duration_between_frames() {
+    let mut app = headless_app(ScriptedSource::new(vec![
+        press(KEY, Duration::from_millis(105)),
+        release(KEY, Duration::from_millis(305)),
+    ]));
+    run_until(&mut app, Duration::from_millis(400));
+
+    let [(trail, transform)] = trails(&mut app)[..] else {
+        panic!("expected a single trail");
+    };
+    assert_eq!(trail.released_at, Some(Duration::from_millis(305)));
+    assert_close(transform.scale.y, 0.2 * trail_speed(&app));
+    let far_end = (now(&app) - Duration::from_millis(105)).as_secs_f32() * trail_speed(&app);
+    assert_close(transform.translation.y + transform.scale.y / 2.0, trail.origin.y + far_end);
+}
+
+// Check that the default keys sit on the edge the trails move away from, with Q's center at `q`,
+// and that a 300 millisecond tap of Q grows, moves and leaves the window towards `direction`.
+fn check_trail_direction(direction: TrailDirection, q: Vec2) {
+    let mut config = Config::default();
+    config.layout.direction = direction;
+    let vertical = direction.is_vertical();
+    let size = window_size(&config);
+    assert_eq!(size, if vertical { Vec2::new(280.0, 600.0) } else { Vec2::new(600.0, 280.0) });
+
+    let mut app = headless_app_with(config, ScriptedSource::new(vec![press(KEY, Duration::ZERO), release(KEY, Duration::from_millis(300))]));
+    let world = app.world_mut();
+    let (position, start) = world
+        .query::<(&KeyID, &Transform, &TrailStart)>()
+        .iter(world)
+        .find(|(key_id, ..)| key_id.0 == KEY)
+        .map(|(_, transform, start)| (transform.translation.truncate(), *start))
+        .expect("key is spawned");
+    let vector = direction.vector();
+    assert_eq!(position, q);
+    assert_eq!(start.origin, q + vector * 40.0);
+
+    // While held, the trail grows from the key's edge towards the direction.
+    run_until(&mut app, Duration::from_millis(200));
+    let [(trail, transform)] = trails(&mut app)[..] else {
+        panic!("expected a single trail");
+    };
+    let length = now(&app).as_secs_f32() * trail_speed(&app);
+    assert!(trail.is_active());
+    assert_close(transform.scale.y, length);
+    assert_close((transform.rotation * Vec3::Y).truncate().distance(vector), 0.0);
+    assert_close(transform.translation.truncate().distance(trail.origin + vector * length / 2.0), 0.0);
+
+    // Once released, both ends move away from the key.
+    run_until(&mut app, Duration::from_secs(1));
+    let [(_, transform)] = trails(&mut app)[..] else {
+        panic!("expected a single trail");
+    };
+    let far = now(&app).as_secs_f32() * trail_speed(&app);
+    let near = far - 0.3 * trail_speed(&app);
+    assert_close(transform.scale.y, far - near);
+    assert_close(transform.translation.truncate().distance(start.origin + vector * (near + far) / 2.0), 0.0);
+
+    // The near end starts 220 pixels before the center and leaves the far edge 300 pixels past it,
+    // 520 pixels or 2.08 seconds after the release.
+    run_until(&mut app, Duration::from_millis(2_350));
+    assert_eq!(trails(&mut app).len(), 1);
+    run_until(&mut app, Duration::from_millis(2_450));
+    assert!(trails(&mut app).is_empty());
+}
+
+#[test]
+fn downward_trails_fall_from_keys_at_the_top() {
+    check_trail_direction(TrailDirection::Down, Vec2::new(-90.0, 260.0));
+}
+
+#[test]
+fn leftward_trails_leave_keys_on_the_right() {
+    check_trail_direction(TrailDirection::Left, Vec2::new(260.0, 90.0));
+}
+
+#[test]
+fn rightward_trails_leave_keys_on_the_left() {
+    check_trail_direction(TrailDirection::Right, Vec2::new(-260.0, 90.0));
+}
+
+#[test]
+fn released_trails_despawn_once_out_of_the_window() {
+    let mut app = headless_app(ScriptedSource::new(vec![press(KEY, Duration::ZERO), release(KEY, Duration::from_millis(100))]));
+    run_until(&mut app, Duration::from_secs(1));
+    assert_eq!(trails(&mut app).len(), 1);
+
+    // The window is 600 pixels tall, which the trail's near end crosses in under three seconds.
+    run_until(&mut app, Duration::from_secs(3));
+    assert!(trails(&mut app).is_empty());
+    assert_eq!(clicks(&mut app, KEY), 1);
+}
+
+#[test]
+fn held_trails_stay_past_the_window() {
+    let mut app = headless_app(ScriptedSource::new(vec![press(KEY, Duration::ZERO)]));
+    run_until(&mut app, Duration::from_secs(5));
+    assert_eq!(trails(&mut app).len(), 1);
+}
+
+#[test]
+fn releases_report_how_long_keys_were_held() {
+    let mut app = headless_app(ScriptedSource::new(vec![
+        press(KEY, Duration::from_millis(105)),
+        release(KEY, Duration::from_millis(305)),
+    ]));
+    run_until(&mut app, Duration::from_millis(305));
+
+    let events = app.world().resource::<Events<KeyReleased>>();
+    let released: Vec<KeyReleased> = events.get_reader().read(events).copied().collect();
+    let [event] = released[..] else {
+        panic!("expected a single release, got {released:?}");
+    };
+    assert_eq!(event.binding, KEY);
+    assert_eq!(event.held, Duration::from_millis(200));
+    assert_eq!(app.world().get::<KeyID>(event.key).map(|key_id| key_id.0), Some(KEY));
+}
+
+#[test]
+fn rows_stack_keys_and_size_the_window() {
+    // Q and W on the row nearest to the trails, and a double-width space bar below them.
+    let key = |code| KeyConfig::new(Binding::Key(code), Color::WHITE);
+    let config = Config {
+        keys: vec![
+            key(KeyCode::KeyQ),
+            key(KeyCode::KeyW),
+            KeyConfig { row: 1, width: Some(2.0), ..key(KeyCode::Space) },
+        ],
+        ..Default::default()
+    };
+    // Keys are 80 pixels with 10 pixel gaps, so the block is 170 pixels square.
+    assert_eq!(window_size(&config), Vec2::new(190.0, 690.0));
+
+    let mut app = headless_app_with(config, ScriptedSource::new(Vec::new()));
+    let world = app.world_mut();
+    let keys: Vec<(Binding, Vec3, TrailStart)> = world
+        .query::<(&KeyID, &Transform, &TrailStart)>()
+        .iter(world)
+        .map(|(key_id, transform, start)| (key_id.0, transform.translation, *start))
+        .collect();
+    let find = |code| keys.iter().find(|(binding, ..)| *binding == Binding::Key(code)).copied().expect("key is spawned");
+
+    let (_, q, _) = find(KeyCode::KeyQ);
+    assert_eq!(q.truncate(), Vec2::new(-45.0, -215.0));
+    let (_, space, space_start) = find(KeyCode::Space);
+    assert_eq!(space.truncate(), Vec2::new(0.0, -305.0));
+    assert_eq!(space_start.origin, Vec2::new(0.0, -265.0));
+    assert_eq!(space_start.width, 170.0);
+}
+
+#[test]
+fn reloading_resizes_the_window_to_the_new_layout() {
+    let mut app = headless_app(ScriptedSource::new(Vec::new()));
+    let mut config = Config::default();
+    config.keys.extend([KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyF].map(|code| KeyConfig::new(Binding::Key(code), Color::WHITE)));
+    let size = window_size(&config);
+    app.insert_resource(config);
+    app.world_mut().send_event(ConfigReloaded);
+    app.update();
+
+    let world = app.world_mut();
+    let window = world.query_filtered::<&Window, With<PrimaryWindow>>().single(world);
+    assert_eq!(Vec2::new(window.width(), window.height()), size);
+    assert_eq!(world.query::<&KeyID>().iter(world).count(), 7);
+}
+
+#[test]
+fn gamepad_buttons_and_triggers_press_their_bindings() {
+    let south: Binding = "GamepadSouth".parse().unwrap();
+    let trigger: Binding = "Gamepad0RightTrigger2".parse().unwrap();
+    let config = Config {
+        keys: vec![KeyConfig::new(south, Color::WHITE), KeyConfig::new(trigger, Color::WHITE)],
+        ..Default::default()
+    };
+    let mut app = headless_app_with(config, WindowInput::default());
+    for id in [0, 1] {
+        let info = GamepadInfo { name: format!("Gamepad {id}") };
+        let event = GamepadConnectionEvent::new(Gamepad::new(id), GamepadConnection::Connected(info));
+        app.world_mut().send_event(GamepadEvent::from(event));
+    }
+    app.update();
+
+    // Bindings without a gamepad id match every gamepad.
+    set_gamepad_button(&mut app, 1, GamepadButtonType::South, 1.0);
+    assert!(held(&mut app, south));
+    set_gamepad_button(&mut app, 1, GamepadButtonType::South, 0.0);
+    assert!(!held(&mut app, south));
+    assert_eq!(clicks(&mut app, south), 1);
+
+    // The default threshold is half way.
+    set_gamepad_button(&mut app, 0, GamepadButtonType::RightTrigger2, 0.4);
+    assert!(!held(&mut app, trigger));
+    set_gamepad_button(&mut app, 0, GamepadButtonType::RightTrigger2, 0.6);
+    assert!(held(&mut app, trigger));
+    set_gamepad_button(&mut app, 0, GamepadButtonType::RightTrigger2, 0.3);
+    assert!(!held(&mut app, trigger));
+
+    // Bindings with a gamepad id ignore the other gamepads.
+    set_gamepad_button(&mut app, 1, GamepadButtonType::RightTrigger2, 0.9);
+    assert!(!held(&mut app, trigger));
+    assert_eq!(clicks(&mut app, trigger), 1);
+}
+
+#[test]
+fn seeking_a_replay_restores_counts_and_held_keys() {
+    // Q is tapped twice, then W is held from 4 to 9 seconds.
+    let w = Binding::Key(KeyCode::KeyW);
+    let mut replay = Replay::new();
+    replay.events = [(0, KEY, true), (1_000, KEY, false), (2_000, KEY, true), (3_000, KEY, false), (4_000, w, true), (9_000, w, false)]
+        .map(|(millis, binding, pressed)| ReplayEvent {
+            time: Duration::from_millis(millis),
+            binding,
+            state: if pressed { ButtonState::Pressed } else { ButtonState::Released },
+        })
+        .to_vec();
+
+    let mut app = unstarted_app(Config::default(), ReplaySource::new(replay));
+    app.add_event::<SeekReplay>()
+        .add_event::<ReplaySeeked>()
+        .add_systems(Update, reset_after_seek.before(update_keys));
+    app.update();
+    run_until(&mut app, Duration::from_millis(500));
+    assert_eq!(clicks(&mut app, KEY), 1);
+
+    // Forward past the taps and into the hold of W.
+    app.world_mut().send_event(SeekReplay(5.0));
+    app.update();
+    assert_eq!((clicks(&mut app, KEY), clicks(&mut app, w)), (2, 1));
+    let bindings = app.world().resource::<ButtonInput<Binding>>();
+    assert!(bindings.pressed(w) && !bindings.pressed(KEY));
+    assert!(!bindings.just_pressed(w), "seeking is not a new press");
+    assert!(trails(&mut app).is_empty());
+
+    // Back into the first tap of Q.
+    app.world_mut().send_event(SeekReplay(-5.0));
+    app.update();
+    assert_eq!((clicks(&mut app, KEY), clicks(&mut app, w)), (1, 0));
+    let bindings = app.world().resource::<ButtonInput<Binding>>();
+    assert!(bindings.pressed(KEY) && !bindings.pressed(w));
+}
+
+#[test]
+fn live_input_only_drives_the_hotkeys() {
+    let mute = Config::default().hotkeys.mute;
+    let mut app = headless_app(HotkeysOnly::new(WindowInput::default()));
+    let f7 = KeyboardInput { key_code: KeyCode::F7, logical_key: Key::F7, ..keyboard(ButtonState::Pressed) };
+    app.world_mut().send_event_batch([keyboard(ButtonState::Pressed), f7]);
+    app.update();
+
+    assert_eq!(clicks(&mut app, KEY), 0);
+    assert!(trails(&mut app).is_empty());
+    let bindings = app.world().resource::<ButtonInput<Binding>>();
+    assert!(bindings.just_pressed(mute) && !bindings.pressed(KEY));
+}
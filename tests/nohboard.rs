@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use clicky_rs::binding::Binding;
+use clicky_rs::config::Config;
+use clicky_rs::nohboard;
+
+// A rectangular NohBoard element with the given type, key codes, text and pixel bounds.
+fn element(id: u32, kind: &str, key_codes: &[u32], text: &str, [left, top, right, bottom]: [f32; 4]) -> String {
+    format!(
+        r#"{{
+            "__type": "{kind}Definition:#ThoNohT.NohBoard.Keyboard.ElementDefinitions",
+            "Id": {id},
+            "Boundaries": [{{"X": {left}, "Y": {top}}}, {{"X": {right}, "Y": {top}}}, {{"X": {right}, "Y": {bottom}}}, {{"X": {left}, "Y": {bottom}}}],
+            "KeyCodes": {key_codes:?},
+            "Text": "{text}",
+            "TextPosition": {{"X": {left}, "Y": {top}}}
+        }}"#
+    )
+}
+
+fn definition(elements: &[String]) -> String {
+    format!(r#"{{"Version": 2, "Width": 200, "Height": 150, "Elements": [{}]}}"#, elements.join(","))
+}
+
+#[test]
+fn keys_keep_their_place_size_and_text() {
+    // 40 pixel keys with 4 pixel gaps: Q, W and the left mouse button on top, a double-width space bar below.
+    let source = definition(&[
+        element(1, "KeyboardKey", &[0x51], "Q", [0.0, 0.0, 40.0, 40.0]),
+        element(2, "KeyboardKey", &[0x57], "Up", [44.0, 0.0, 84.0, 40.0]),
+        element(3, "KeyboardKey", &[0x20], "", [0.0, 44.0, 84.0, 84.0]),
+        element(4, "MouseKey", &[0], "LMB", [100.0, 0.0, 140.0, 40.0]),
+    ]);
+    let import = nohboard::import(&source).expect("definition imports");
+    assert!(import.warnings.is_empty(), "unexpected warnings {:?}", import.warnings);
+    assert_eq!((import.key_size, import.key_spacing), (40.0, 4.0));
+
+    let config: Config = toml::from_str(&import.to_toml()).expect("import is a valid configuration");
+    config.validate().expect("imported configuration validates");
+    let keys: Vec<_> = config.keys.iter().map(|key| (key.code, key.label.as_deref(), key.row, key.x, key.width)).collect();
+    assert_eq!(
+        keys,
+        [
+            (Binding::Key(KeyCode::KeyQ), None, 0, None, None),
+            (Binding::Key(KeyCode::KeyW), Some("Up"), 0, None, None),
+            (Binding::Mouse(MouseButton::Left), None, 0, Some(0.27), None),
+            (Binding::Key(KeyCode::Space), None, 1, None, Some(2.0)),
+        ]
+    );
+}
+
+#[test]
+fn unsupported_elements_are_reported() {
+    let source = definition(&[
+        element(1, "KeyboardKey", &[0x51], "Q", [0.0, 0.0, 40.0, 40.0]),
+        element(2, "KeyboardKey", &[0x51], "Q", [44.0, 0.0, 84.0, 40.0]),
+        element(3, "KeyboardKey", &[0xFF], "Fn", [88.0, 0.0, 128.0, 40.0]),
+        element(4, "MouseScroll", &[2], "", [132.0, 0.0, 172.0, 40.0]),
+        r#"{"__type": "MouseSpeedIndicatorDefinition:#ThoNohT", "Id": 5, "Location": {"X": 20, "Y": 80}, "Radius": 20}"#.to_string(),
+    ]);
+    let import = nohboard::import(&source).expect("definition imports");
+    assert_eq!(import.keys.len(), 1);
+    assert_eq!(
+        import.warnings,
+        [
+            "element 2 (KeyboardKey \"Q\") duplicates the KeyQ key",
+            "element 3 (KeyboardKey \"Fn\") has no supported key code in [255]",
+            "element 4 (MouseScroll) has no supported key code in [2]",
+            "element 5 (MouseSpeedIndicator) is not supported",
+        ]
+    );
+}
+
+#[test]
+fn definitions_without_keys_are_rejected() {
+    assert!(nohboard::import(&definition(&[])).is_err());
+    let err = nohboard::import(r#"{"keyviz": {"style": "minimal"}}"#).err().expect("other files are rejected");
+    assert!(err.contains("only NohBoard keyboard.json files can be imported"), "{err}");
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use clicky_rs::binding::Binding;
+use clicky_rs::persistence::ClickStore;
+
+const Q: Binding = Binding::Key(KeyCode::KeyQ);
+const W: Binding = Binding::Key(KeyCode::KeyW);
+
+// A counts file in the temporary directory, unique to this test run.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("clicky-counts-{}-{name}.toml", std::process::id()))
+}
+
+// The lifetime and last session counts saved for `binding`.
+fn saved(path: &Path, binding: Binding) -> (i64, i64) {
+    let saved: toml::Table = toml::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let key = saved["keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|key| key["code"].as_str() == Some(&binding.to_string()))
+        .expect("binding is saved");
+    (key["total"].as_integer().unwrap(), key["session"].as_integer().unwrap())
+}
+
+#[test]
+fn counts_survive_a_restart() {
+    let path = temp_path("restart");
+    let mut store = ClickStore::at(path.clone());
+    assert!(store.totals().is_empty());
+    store.record(Q);
+    store.record(Q);
+    store.record(W);
+    store.save();
+
+    let loaded = ClickStore::at(path.clone());
+    fs::remove_file(&path).ok();
+    assert_eq!(loaded.totals().get(&Q), Some(&2));
+    assert_eq!(loaded.totals().get(&W), Some(&1));
+}
+
+#[test]
+fn session_counts_are_kept_apart_from_totals() {
+    let path = temp_path("session");
+    fs::write(&path, "[[keys]]\ncode = \"KeyQ\"\ntotal = 5\nsession = 5\n").unwrap();
+
+    let mut store = ClickStore::at(path.clone());
+    assert_eq!(store.record(Q), 6);
+    assert_eq!(store.record(Q), 7);
+    store.save();
+    let counts = saved(&path, Q);
+    fs::remove_file(&path).ok();
+    assert_eq!(counts, (7, 2));
+}
+
+#[test]
+fn unreadable_counts_are_never_overwritten() {
+    let path = temp_path("unreadable");
+    let contents = "keys = \"not a list\"";
+    fs::write(&path, contents).unwrap();
+
+    let mut store = ClickStore::at(path.clone());
+    assert!(store.totals().is_empty());
+    store.record(Q);
+    store.save();
+    let after = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).ok();
+    assert_eq!(after, contents);
+}
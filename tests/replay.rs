@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+use clicky_rs::binding::Binding;
+use clicky_rs::replay::{Replay, ReplayError, ReplayEvent, REPLAY_VERSION};
+
+// A file in the temporary directory, unique to this test run.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("clicky-replay-{}-{name}", std::process::id()))
+}
+
+fn event(millis: u64, binding: Binding, state: ButtonState) -> ReplayEvent {
+    ReplayEvent { time: Duration::from_millis(millis), binding, state }
+}
+
+fn summary(replay: &Replay) -> Vec<(Duration, Binding, ButtonState)> {
+    replay.events.iter().map(|event| (event.time, event.binding, event.state)).collect()
+}
+
+#[test]
+fn binary_and_json_replays_round_trip() {
+    let mut replay = Replay::new();
+    replay.events = vec![
+        event(0, Binding::Key(KeyCode::KeyQ), ButtonState::Pressed),
+        event(120, Binding::Mouse(MouseButton::Back), ButtonState::Pressed),
+        event(250, Binding::Key(KeyCode::KeyQ), ButtonState::Released),
+        event(250, Binding::ScrollDown, ButtonState::Pressed),
+        event(1_000, "Gamepad1RightTrigger2".parse().unwrap(), ButtonState::Pressed),
+    ];
+
+    for name in ["round-trip.clkr", "round-trip.json"] {
+        let path = temp_path(name);
+        replay.save(&path).expect("replay saves");
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).ok();
+
+        let loaded = loaded.expect("replay loads");
+        assert_eq!(loaded.version, REPLAY_VERSION);
+        assert_eq!(summary(&loaded), summary(&replay), "{name} changed the events");
+    }
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let newer = REPLAY_VERSION + 1;
+    let binary = [b"CLKR".as_slice(), &newer.to_le_bytes()].concat();
+    let json = format!(r#"{{"version": {newer}, "events": []}}"#);
+
+    for (name, contents) in [("newer.clkr", binary), ("newer.json", json.into_bytes())] {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        let result = Replay::load(&path);
+        fs::remove_file(&path).ok();
+
+        assert!(matches!(result, Err(ReplayError::UnsupportedVersion(_, version)) if version == newer), "{name}: {result:?}");
+    }
+}
+
+#[test]
+fn binding_indexes_past_the_table_are_rejected() {
+    // One binding, and an event pointing at a second one.
+    let name = b"KeyQ";
+    let mut bytes = b"CLKR".to_vec();
+    bytes.extend(REPLAY_VERSION.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.push(name.len() as u8);
+    bytes.extend(name);
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(0u64.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.push(1);
+
+    let path = temp_path("bad-index.clkr");
+    fs::write(&path, bytes).unwrap();
+    let result = Replay::load(&path);
+    fs::remove_file(&path).ok();
+
+    assert!(matches!(&result, Err(ReplayError::Parse(_, message)) if message.contains("invalid binding index 1")), "{result:?}");
+}